@@ -5,6 +5,17 @@ PROJECT_NAME=$PROJECT_NAME
 BUILD_IDL=$BUILD_IDL
 MANIFEST_PATH=$MANIFEST_PATH
 BASE_PATH=$BASE_PATH
+GIT_REF=$GIT_REF
+# `fetch` clones the repository and downloads dependencies, `compile` builds the project offline
+BUILD_PHASE=$BUILD_PHASE
+
+# Must be in sync with the exit codes in src/processor/docker.rs
+EXIT_GIT_REF_NOT_FOUND=10
+EXIT_CLONE_FAILED=11
+EXIT_MANIFEST_NOT_FOUND=12
+EXIT_BUILD_FAILED=13
+EXIT_IDL_FAILED=14
+EXIT_DEPENDENCIES_FAILED=15
 
 MNT_DIR="/mnt/target"
 ROOT_DIR="/project"
@@ -12,19 +23,13 @@ APP_DIR="/app"
 TARGET_DIR="$APP_DIR/target"
 RELEASE_DIR="$TARGET_DIR/wasm32-gear/release"
 
+echo "Build phase: $BUILD_PHASE"
 echo "Target directory: $TARGET_DIR"
 echo "Release directory: $RELEASE_DIR"
 echo "Base path: $BASE_PATH"
 echo "Manifest path: $MANIFEST_PATH"
 echo "Project name: $PROJECT_NAME"
-
-echo "Cloning repository $REPO_URL into $ROOT_DIR"
-git clone --depth 1 "$REPO_URL" "$ROOT_DIR"
-
-if [ $? -ne 0 ]; then
-    echo "Error: Failed to clone the repository $REPO_URL" >&2
-    exit 1
-fi
+echo "Git ref: $GIT_REF"
 
 base_path="$ROOT_DIR"
 
@@ -39,54 +44,143 @@ if [ -n "$BASE_PATH" ]; then
     esac
 fi
 
-echo "Moving $base_path to $APP_DIR"
-rm -r "$APP_DIR"
-mv "$base_path" "$APP_DIR"
+fetch() {
+    echo "Cloning repository $REPO_URL into $ROOT_DIR"
+    if [ -n "$GIT_REF" ]; then
+        # Arbitrary commits can't be fetched from a shallow clone
+        git clone --no-checkout "$REPO_URL" "$ROOT_DIR"
+    else
+        git clone --depth 1 "$REPO_URL" "$ROOT_DIR"
+    fi
+
+    if [ $? -ne 0 ]; then
+        echo "Error: Failed to clone the repository $REPO_URL" >&2
+        exit $EXIT_CLONE_FAILED
+    fi
 
-cd "$APP_DIR"
-echo "Changing directory to $APP_DIR"
-ls -l
+    if [ -n "$GIT_REF" ]; then
+        # Branches are only available as remote-tracking refs after clone
+        commit=$(git -C "$ROOT_DIR" rev-parse --verify --quiet "$GIT_REF^{commit}" \
+            || git -C "$ROOT_DIR" rev-parse --verify --quiet "origin/$GIT_REF^{commit}")
 
-args=
+        if [ -z "$commit" ]; then
+            echo "Error: Git ref $GIT_REF not found in the repository $REPO_URL" >&2
+            exit $EXIT_GIT_REF_NOT_FOUND
+        fi
 
-if [ -n "$PROJECT_NAME" ]; then
-    echo "Using project name: $PROJECT_NAME"
-    args="-p $PROJECT_NAME"
-elif [ -n "$MANIFEST_PATH" ]; then
-    echo "Using manifest path: $MANIFEST_PATH"
-    if [ ! -f "$MANIFEST_PATH" ]; then
-        echo "Error: Manifest path $MANIFEST_PATH not found" >&2
-        exit 1
+        echo "Checking out $GIT_REF ($commit)"
+        git -C "$ROOT_DIR" checkout --quiet --detach "$commit"
+
+        if [ $? -ne 0 ]; then
+            echo "Error: Failed to checkout $GIT_REF" >&2
+            exit 1
+        fi
     fi
-    args="--manifest-path $MANIFEST_PATH"
-elif [ -f "Cargo.toml" ]; then
-    echo "Using root Cargo.toml"
-    args=""
-else
-    echo "Error: Cargo.toml not found in the current directory, cannot resolve project" >&2
-    exit 1
-fi
 
-echo "Run cargo build with $args --target-dir $TARGET_DIR"
-cargo build --release $args --target-dir "$TARGET_DIR" --locked
+    commit_sha=$(git -C "$ROOT_DIR" rev-parse HEAD)
+    echo "Commit SHA: $commit_sha"
+    echo "$commit_sha" > "$MNT_DIR/commit_sha"
 
-if [ $? -ne 0 ]; then
-    echo "Error: Failed to build the project"
-    exit 1
-fi
+    cd "$base_path"
 
-if [ "$BUILD_IDL" = "true" ]; then
-    echo "Building the idl"
-    cargo-sails sails idl $args --target-dir "$MNT_DIR"
     if [ $? -ne 0 ]; then
-        exit 1
+        echo "Error: Base path $BASE_PATH not found" >&2
+        exit $EXIT_MANIFEST_NOT_FOUND
     fi
-fi
 
-echo "=== $RELEASE_DIR ==="
-ls -al "$RELEASE_DIR"
-echo "Copying files..."
-cp "$RELEASE_DIR"/* "$MNT_DIR"
+    echo "Changing directory to $base_path"
+
+    fetch_args=
 
-echo "=== $MNT_DIR ==="
-ls -al "$MNT_DIR"
+    if [ -n "$MANIFEST_PATH" ]; then
+        if [ ! -f "$MANIFEST_PATH" ]; then
+            echo "Error: Manifest path $MANIFEST_PATH not found" >&2
+            exit $EXIT_MANIFEST_NOT_FOUND
+        fi
+        fetch_args="--manifest-path $MANIFEST_PATH"
+    elif [ ! -f "Cargo.toml" ]; then
+        echo "Error: Cargo.toml not found in $base_path" >&2
+        exit $EXIT_MANIFEST_NOT_FOUND
+    fi
+
+    echo "Run cargo fetch $fetch_args"
+    cargo fetch $fetch_args --locked
+
+    if [ $? -ne 0 ]; then
+        echo "Error: Failed to fetch dependencies of the project" >&2
+        exit $EXIT_DEPENDENCIES_FAILED
+    fi
+}
+
+compile() {
+    # The project is built at the same path as with the builder image used manually,
+    # otherwise paths embedded into the wasm would differ
+    echo "Copying $base_path to $APP_DIR"
+    rm -r "$APP_DIR"
+    cp -a "$base_path" "$APP_DIR"
+
+    cd "$APP_DIR"
+    echo "Changing directory to $APP_DIR"
+    ls -l
+
+    args=
+
+    if [ -n "$PROJECT_NAME" ]; then
+        echo "Using project name: $PROJECT_NAME"
+        args="-p $PROJECT_NAME"
+    elif [ -n "$MANIFEST_PATH" ]; then
+        echo "Using manifest path: $MANIFEST_PATH"
+        if [ ! -f "$MANIFEST_PATH" ]; then
+            echo "Error: Manifest path $MANIFEST_PATH not found" >&2
+            exit $EXIT_MANIFEST_NOT_FOUND
+        fi
+        args="--manifest-path $MANIFEST_PATH"
+    elif [ -f "Cargo.toml" ]; then
+        echo "Using root Cargo.toml"
+        args=""
+    else
+        echo "Error: Cargo.toml not found in the current directory, cannot resolve project" >&2
+        exit $EXIT_MANIFEST_NOT_FOUND
+    fi
+
+    # Nested cargo invocations of the wasm builder inherit it as well
+    export CARGO_NET_OFFLINE=true
+
+    echo "Run cargo build with $args --target-dir $TARGET_DIR"
+    cargo build --release $args --target-dir "$TARGET_DIR" --locked
+
+    if [ $? -ne 0 ]; then
+        echo "Error: Failed to build the project"
+        exit $EXIT_BUILD_FAILED
+    fi
+
+    if [ "$BUILD_IDL" = "true" ]; then
+        echo "Building the idl"
+        cargo-sails sails idl $args --target-dir "$MNT_DIR"
+        if [ $? -ne 0 ]; then
+            echo "Error: Failed to build the idl" >&2
+            exit $EXIT_IDL_FAILED
+        fi
+    fi
+
+    echo "=== $RELEASE_DIR ==="
+    ls -al "$RELEASE_DIR"
+    echo "Copying files..."
+    cp "$RELEASE_DIR"/* "$MNT_DIR"
+
+    echo "=== $MNT_DIR ==="
+    ls -al "$MNT_DIR"
+}
+
+case "$BUILD_PHASE" in
+    fetch)
+        fetch
+        ;;
+    compile)
+        compile
+        ;;
+    *)
+        echo "Error: Unknown build phase $BUILD_PHASE" >&2
+        exit 1
+        ;;
+esac
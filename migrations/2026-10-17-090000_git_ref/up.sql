@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN git_ref VARCHAR;
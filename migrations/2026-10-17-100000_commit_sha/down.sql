@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP COLUMN commit_sha;
+ALTER TABLE code DROP COLUMN commit_sha;
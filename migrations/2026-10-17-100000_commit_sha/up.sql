@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN commit_sha VARCHAR;
+ALTER TABLE code ADD COLUMN commit_sha VARCHAR;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+CREATE TABLE "sails_version"(
+	"version" VARCHAR NOT NULL PRIMARY KEY,
+	"created_at" TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO "sails_version" ("version") VALUES ('0.8.0'), ('0.8.1');
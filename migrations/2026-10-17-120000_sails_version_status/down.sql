@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE sails_version DROP COLUMN status;
+
+DROP TYPE IF EXISTS IMAGESTATUS;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+CREATE TYPE IMAGESTATUS AS ENUM ('building', 'ready', 'failed');
+
+ALTER TABLE sails_version ADD COLUMN status IMAGESTATUS NOT NULL DEFAULT 'building';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+CREATE TYPE NETWORK AS ENUM ('vara_mainnet', 'vara_testnet');
+
+ALTER TABLE verification ALTER COLUMN network TYPE NETWORK USING network::NETWORK;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ALTER COLUMN network TYPE VARCHAR USING network::TEXT;
+
+DROP TYPE NETWORK;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP COLUMN built_code_len;
+ALTER TABLE verification DROP COLUMN built_code_hash;
+ALTER TABLE verification DROP COLUMN onchain_code_len;
+ALTER TABLE verification DROP COLUMN onchain_code_hash;
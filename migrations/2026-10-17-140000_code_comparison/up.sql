@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN built_code_len INTEGER;
+ALTER TABLE verification ADD COLUMN built_code_hash VARCHAR;
+ALTER TABLE verification ADD COLUMN onchain_code_len INTEGER;
+ALTER TABLE verification ADD COLUMN onchain_code_hash VARCHAR;
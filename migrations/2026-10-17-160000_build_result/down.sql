@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP COLUMN calculated_code_id;
+ALTER TABLE verification DROP COLUMN wasm_name;
+ALTER TABLE verification DROP COLUMN artifact_sizes;
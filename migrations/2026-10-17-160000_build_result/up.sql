@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN calculated_code_id VARCHAR;
+ALTER TABLE verification ADD COLUMN wasm_name VARCHAR;
+ALTER TABLE verification ADD COLUMN artifact_sizes JSONB;
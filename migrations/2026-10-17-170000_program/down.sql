@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM verification WHERE code_id IS NULL;
+ALTER TABLE verification ALTER COLUMN code_id SET NOT NULL;
+ALTER TABLE verification DROP COLUMN program_id;
+
+DROP TABLE IF EXISTS "program";
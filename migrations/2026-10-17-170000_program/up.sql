@@ -0,0 +1,11 @@
+-- Your SQL goes here
+
+CREATE TABLE "program"(
+	"id" VARCHAR NOT NULL PRIMARY KEY,
+	"code_id" VARCHAR NOT NULL,
+	"network" VARCHAR NOT NULL,
+	"created_at" TIMESTAMP NOT NULL
+);
+
+ALTER TABLE verification ADD COLUMN program_id VARCHAR;
+ALTER TABLE verification ALTER COLUMN code_id DROP NOT NULL;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+CREATE TABLE "code_programs"(
+	"code_id" VARCHAR NOT NULL,
+	"network" VARCHAR NOT NULL,
+	"program_ids" TEXT[] NOT NULL,
+	"updated_at" TIMESTAMP NOT NULL,
+	PRIMARY KEY ("code_id", "network")
+);
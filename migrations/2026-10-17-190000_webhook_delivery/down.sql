@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS "webhook_delivery";
+
+ALTER TABLE verification DROP COLUMN callback_url;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN callback_url VARCHAR;
+
+CREATE TABLE "webhook_delivery"(
+	"verification_id" VARCHAR NOT NULL PRIMARY KEY REFERENCES verification(id),
+	"url" VARCHAR NOT NULL,
+	"attempts" INT4 NOT NULL,
+	"next_attempt_at" TIMESTAMP,
+	"delivered_at" TIMESTAMP,
+	"last_error" TEXT,
+	"created_at" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX webhook_delivery_next_attempt_at_idx ON webhook_delivery(next_attempt_at);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+CREATE TABLE "api_key"(
+	"id" VARCHAR NOT NULL PRIMARY KEY,
+	"name" VARCHAR NOT NULL,
+	"key_hash" VARCHAR NOT NULL UNIQUE,
+	"scopes" TEXT[] NOT NULL,
+	"created_at" TIMESTAMP NOT NULL,
+	"revoked_at" TIMESTAMP
+);
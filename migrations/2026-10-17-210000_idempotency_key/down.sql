@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS verification_repo_link_idx;
+
+ALTER TABLE verification DROP COLUMN idempotency_key;
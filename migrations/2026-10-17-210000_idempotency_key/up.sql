@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN idempotency_key VARCHAR UNIQUE;
+
+CREATE INDEX verification_repo_link_idx ON verification(repo_link);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS verification_network_idx;
+DROP INDEX IF EXISTS verification_status_idx;
+DROP INDEX IF EXISTS verification_code_id_idx;
+DROP INDEX IF EXISTS verification_created_at_id_idx;
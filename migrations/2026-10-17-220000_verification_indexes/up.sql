@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+CREATE INDEX verification_created_at_id_idx ON verification(created_at DESC, id DESC);
+CREATE INDEX verification_code_id_idx ON verification(code_id, created_at DESC);
+CREATE INDEX verification_status_idx ON verification(status, created_at DESC);
+CREATE INDEX verification_network_idx ON verification(network, created_at DESC);
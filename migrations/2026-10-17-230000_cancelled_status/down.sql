@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+
+UPDATE verification SET status = 'failed', failed_reason = 'Cancelled' WHERE status = 'cancelled';
+
+ALTER TYPE VERIFICATIONSTATUS RENAME TO VERIFICATIONSTATUS_OLD;
+
+CREATE TYPE VERIFICATIONSTATUS AS ENUM ('pending', 'in_progress', 'failed', 'verified');
+
+ALTER TABLE verification ALTER COLUMN status TYPE VERIFICATIONSTATUS USING status::TEXT::VERIFICATIONSTATUS;
+
+DROP TYPE VERIFICATIONSTATUS_OLD;
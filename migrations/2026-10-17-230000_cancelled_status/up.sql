@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TYPE VERIFICATIONSTATUS ADD VALUE 'cancelled';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP COLUMN next_attempt_at;
+ALTER TABLE verification DROP COLUMN attempts;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE verification ADD COLUMN next_attempt_at TIMESTAMP;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP COLUMN failure_kind;
+
+DROP TYPE FAILUREKIND;
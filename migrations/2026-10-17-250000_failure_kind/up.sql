@@ -0,0 +1,37 @@
+-- Your SQL goes here
+
+CREATE TYPE FAILUREKIND AS ENUM (
+	'clone_failed',
+	'manifest_not_found',
+	'build_failed',
+	'idl_failed',
+	'wasm_missing',
+	'code_mismatch',
+	'code_not_onchain',
+	'timeout',
+	'internal'
+);
+
+ALTER TABLE verification ADD COLUMN failure_kind FAILUREKIND;
+
+-- Reasons of earlier failures are mapped to the closest kind, removing error dumps from them
+UPDATE verification SET failure_kind = CASE
+	WHEN failed_reason = 'Code ID mismatch' OR failed_reason LIKE 'Built code differs%' THEN 'code_mismatch'
+	WHEN failed_reason IN ('Code doesn''t exist on chain', 'Program doesn''t exist on chain', 'Original code is not found on chain') THEN 'code_not_onchain'
+	WHEN failed_reason LIKE 'Build timed out%' THEN 'timeout'
+	WHEN failed_reason LIKE 'Git ref%' OR failed_reason LIKE 'Failed to clone%' THEN 'clone_failed'
+	WHEN failed_reason LIKE 'Build was killed%' THEN 'build_failed'
+	WHEN failed_reason LIKE 'Failed to build project.%Failed to build wasm%' THEN 'wasm_missing'
+	WHEN failed_reason LIKE 'Failed to build project.%Failed to build idl%' THEN 'idl_failed'
+	WHEN failed_reason LIKE 'Failed to build project.%Failed to wait for container%' THEN 'build_failed'
+	ELSE 'internal'
+END::FAILUREKIND
+WHERE status = 'failed';
+
+UPDATE verification SET failed_reason = CASE failure_kind
+	WHEN 'wasm_missing' THEN 'Build didn''t produce a .opt.wasm file'
+	WHEN 'idl_failed' THEN 'Failed to build the IDL, see the build logs'
+	WHEN 'build_failed' THEN 'Failed to build the project, see the build logs'
+	ELSE 'Internal error while building the project'
+END
+WHERE failed_reason LIKE 'Failed to build project.%';
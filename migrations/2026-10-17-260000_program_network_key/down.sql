@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM program p USING program newer
+	WHERE p.id = newer.id AND p.created_at < newer.created_at;
+
+ALTER TABLE program DROP CONSTRAINT program_pkey;
+ALTER TABLE program ADD PRIMARY KEY (id);
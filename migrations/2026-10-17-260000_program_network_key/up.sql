@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE program DROP CONSTRAINT program_pkey;
+ALTER TABLE program ADD PRIMARY KEY (id, network);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP CONSTRAINT verification_idempotency_key_key;
+
+-- Keys of different callers may be equal, the earliest verification keeps the key
+UPDATE verification v SET idempotency_key = NULL
+    FROM verification earlier
+    WHERE v.idempotency_key = earlier.idempotency_key AND earlier.created_at < v.created_at;
+
+ALTER TABLE verification DROP COLUMN request_hash;
+ALTER TABLE verification DROP COLUMN idempotency_scope;
+ALTER TABLE verification ADD CONSTRAINT verification_idempotency_key_key UNIQUE (idempotency_key);
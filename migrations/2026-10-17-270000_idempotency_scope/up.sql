@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+ALTER TABLE verification DROP CONSTRAINT verification_idempotency_key_key;
+ALTER TABLE verification ADD COLUMN idempotency_scope VARCHAR;
+ALTER TABLE verification ADD COLUMN request_hash VARCHAR;
+ALTER TABLE verification ADD CONSTRAINT verification_idempotency_key_key
+    UNIQUE (idempotency_scope, idempotency_key);
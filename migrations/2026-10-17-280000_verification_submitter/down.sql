@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE verification DROP COLUMN cancel_token_hash;
+ALTER TABLE verification DROP COLUMN submitter;
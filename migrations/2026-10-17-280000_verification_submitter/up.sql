@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE verification ADD COLUMN submitter VARCHAR;
+ALTER TABLE verification ADD COLUMN cancel_token_hash VARCHAR;
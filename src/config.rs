@@ -0,0 +1,516 @@
+use crate::consts::{
+    ARTIFACTS_DIR, DEFAULT_BASE_IMAGE, DEFAULT_BIND_ADDR, DEFAULT_BUILDER_MEMORY,
+    DEFAULT_BUILDER_NANO_CPUS, DEFAULT_BUILDER_PIDS_LIMIT, DEFAULT_BUILD_TIMEOUT_MINUTES,
+    DEFAULT_CHECK_INTERVAL_SECS, DEFAULT_CONFIG_PATH, DEFAULT_FILES_TTL_DAYS, DEFAULT_MAX_ATTEMPTS,
+    DEFAULT_MAX_PENDING_PER_CODE, DEFAULT_MAX_PENDING_PER_REPO, DEFAULT_MAX_VERIFS_IN_PROGRESS,
+    DEFAULT_POOL_SIZE, DEFAULT_PROGRAMS_CACHE_TTL_SECS, DEFAULT_RATE_LIMIT_IP_BURST,
+    DEFAULT_RATE_LIMIT_IP_REFILL_SECS, DEFAULT_RATE_LIMIT_KEY_BURST,
+    DEFAULT_RATE_LIMIT_KEY_REFILL_SECS, DEFAULT_RATE_LIMIT_REPO_BURST,
+    DEFAULT_RATE_LIMIT_REPO_REFILL_SECS, DEFAULT_RETRY_BASE_SECS, DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+    DEFAULT_WEBHOOK_RETRY_BASE_SECS, DEFAULT_WEBHOOK_TIMEOUT_SECS, IMAGE_NAME, LOGS_DIR,
+    PATH_TO_BUILDS,
+};
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::{env, fmt::Display, fs, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
+
+/// Operational settings of the service.
+///
+/// Loaded from the TOML file at `CONFIG_PATH` (default: `config.toml`), every setting
+/// can be overridden with the environment variable listed next to it.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub auth: AuthConfig,
+    pub database: DatabaseConfig,
+    pub processor: ProcessorConfig,
+    pub builder: BuilderConfig,
+    pub webhooks: WebhookConfig,
+    pub rate_limit: RateLimitConfig,
+    pub networks: Vec<NetworkConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the HTTP server listens on (`BIND_ADDR`)
+    pub bind_addr: SocketAddr,
+    /// Token granting all scopes, used to create the first API keys (`ADMIN_TOKEN`)
+    pub admin_token: Option<String>,
+    /// Time in seconds the programs of a code are cached for (`PROGRAMS_CACHE_TTL_SECS`)
+    pub programs_cache_ttl_secs: u64,
+}
+
+impl ServerConfig {
+    pub fn programs_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.programs_cache_ttl_secs)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: DEFAULT_BIND_ADDR.parse().expect("Invalid default address"),
+            admin_token: None,
+            programs_cache_ttl_secs: DEFAULT_PROGRAMS_CACHE_TTL_SECS,
+        }
+    }
+}
+
+/// Access of requests without an API key. Requests with a key are allowed the scopes of the key
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Whether read routes are available without an API key (`AUTH_ANONYMOUS_READ`)
+    pub anonymous_read: bool,
+    /// Whether verifications can be submitted without an API key (`AUTH_ANONYMOUS_SUBMIT`)
+    pub anonymous_submit: bool,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_read: true,
+            anonymous_submit: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Postgres connection URL (`DATABASE_URL`)
+    pub url: String,
+    /// Maximum number of connections in the pool (`DB_POOL_SIZE`)
+    pub pool_size: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            pool_size: DEFAULT_POOL_SIZE,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProcessorConfig {
+    /// Maximum number of verifications processed concurrently (`MAX_VERIFS_IN_PROGRESS`)
+    pub max_verifs_in_progress: i64,
+    /// Interval between checks for pending verifications in seconds (`CHECK_INTERVAL_SECS`)
+    pub check_interval_secs: u64,
+    /// Number of attempts of a verification failing for transient reasons, e.g. network errors,
+    /// before it's failed (`MAX_ATTEMPTS`)
+    pub max_attempts: i32,
+    /// Delay before the first retry in seconds, doubled on every retry (`RETRY_BASE_SECS`)
+    pub retry_base_secs: u64,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            max_verifs_in_progress: DEFAULT_MAX_VERIFS_IN_PROGRESS,
+            check_interval_secs: DEFAULT_CHECK_INTERVAL_SECS,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_base_secs: DEFAULT_RETRY_BASE_SECS,
+        }
+    }
+}
+
+impl ProcessorConfig {
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_secs(self.check_interval_secs)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuilderConfig {
+    /// Directory where build artifacts are stored (`PATH_TO_BUILDS`)
+    pub builds_dir: PathBuf,
+    /// Directory where build logs are stored (`LOGS_DIR`)
+    pub logs_dir: PathBuf,
+    /// Directory where built wasm of mismatched verifications is kept (`ARTIFACTS_DIR`)
+    pub artifacts_dir: PathBuf,
+    /// Name of the verifier images, tagged with the Sails version (`IMAGE_NAME`)
+    pub image_name: String,
+    /// Image the verifier images are built from, tagged with the Sails version (`BASE_IMAGE`)
+    pub base_image: String,
+    /// Memory limit of a builder container in bytes (`BUILDER_MEMORY`)
+    pub memory: i64,
+    /// CPU quota of a builder container in units of 10<sup>-9</sup> CPUs (`BUILDER_NANO_CPUS`)
+    pub nano_cpus: i64,
+    /// Maximum number of processes in a builder container (`BUILDER_PIDS_LIMIT`)
+    pub pids_limit: i64,
+    /// Size of the writable layer of a builder container, e.g. `20G` (`BUILDER_STORAGE_SIZE`).
+    /// Only supported by storage drivers with quotas enabled
+    pub storage_size: Option<String>,
+    /// Wall-clock time after which the build is killed (`BUILD_TIMEOUT_MINUTES`)
+    pub timeout_minutes: u64,
+    /// Days build logs and kept artifacts are stored for (`BUILD_FILES_TTL_DAYS`)
+    pub files_ttl_days: u64,
+}
+
+impl Default for BuilderConfig {
+    fn default() -> Self {
+        Self {
+            builds_dir: PATH_TO_BUILDS.into(),
+            logs_dir: LOGS_DIR.into(),
+            artifacts_dir: ARTIFACTS_DIR.into(),
+            image_name: IMAGE_NAME.to_string(),
+            base_image: DEFAULT_BASE_IMAGE.to_string(),
+            memory: DEFAULT_BUILDER_MEMORY,
+            nano_cpus: DEFAULT_BUILDER_NANO_CPUS,
+            pids_limit: DEFAULT_BUILDER_PIDS_LIMIT,
+            storage_size: None,
+            timeout_minutes: DEFAULT_BUILD_TIMEOUT_MINUTES,
+            files_ttl_days: DEFAULT_FILES_TTL_DAYS,
+        }
+    }
+}
+
+impl BuilderConfig {
+    pub fn build_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_minutes * 60)
+    }
+
+    pub fn files_ttl(&self) -> Duration {
+        Duration::from_secs(self.files_ttl_days * 24 * 60 * 60)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Key of the HMAC signature of callbacks, which are disabled if it's not set (`WEBHOOK_SECRET`)
+    pub secret: Option<String>,
+    /// Number of attempts to deliver a callback before giving up (`WEBHOOK_MAX_ATTEMPTS`)
+    pub max_attempts: i32,
+    /// Delay before the first retry in seconds, doubled on every retry (`WEBHOOK_RETRY_BASE_SECS`)
+    pub retry_base_secs: u64,
+    /// Timeout of a callback request in seconds (`WEBHOOK_TIMEOUT_SECS`)
+    pub timeout_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            max_attempts: DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+            retry_base_secs: DEFAULT_WEBHOOK_RETRY_BASE_SECS,
+            timeout_secs: DEFAULT_WEBHOOK_TIMEOUT_SECS,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+/// Limits of `POST /verify` requests
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Number of requests a client IP can make at once (`RATE_LIMIT_IP_BURST`)
+    pub ip_burst: u32,
+    /// Seconds for a client IP to regain one request (`RATE_LIMIT_IP_REFILL_SECS`)
+    pub ip_refill_secs: u64,
+    /// Number of requests an API key can make at once, instead of the IP limit (`RATE_LIMIT_KEY_BURST`)
+    pub key_burst: u32,
+    /// Seconds for an API key to regain one request (`RATE_LIMIT_KEY_REFILL_SECS`)
+    pub key_refill_secs: u64,
+    /// Number of requests for a repository that can be made at once (`RATE_LIMIT_REPO_BURST`)
+    pub repo_burst: u32,
+    /// Seconds for a repository to regain one request (`RATE_LIMIT_REPO_REFILL_SECS`)
+    pub repo_refill_secs: u64,
+    /// Maximum number of unfinished verifications of a repository (`MAX_PENDING_PER_REPO`)
+    pub max_pending_per_repo: i64,
+    /// Maximum number of unfinished verifications of a code or program (`MAX_PENDING_PER_CODE`)
+    pub max_pending_per_code: i64,
+    /// Whether the client IP is taken from the `X-Forwarded-For` header set by a reverse proxy
+    /// (`RATE_LIMIT_TRUST_FORWARDED_FOR`)
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            ip_burst: DEFAULT_RATE_LIMIT_IP_BURST,
+            ip_refill_secs: DEFAULT_RATE_LIMIT_IP_REFILL_SECS,
+            key_burst: DEFAULT_RATE_LIMIT_KEY_BURST,
+            key_refill_secs: DEFAULT_RATE_LIMIT_KEY_REFILL_SECS,
+            repo_burst: DEFAULT_RATE_LIMIT_REPO_BURST,
+            repo_refill_secs: DEFAULT_RATE_LIMIT_REPO_REFILL_SECS,
+            max_pending_per_repo: DEFAULT_MAX_PENDING_PER_REPO,
+            max_pending_per_code: DEFAULT_MAX_PENDING_PER_CODE,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn ip_refill(&self) -> Duration {
+        Duration::from_secs(self.ip_refill_secs)
+    }
+
+    pub fn key_refill(&self) -> Duration {
+        Duration::from_secs(self.key_refill_secs)
+    }
+
+    pub fn repo_refill(&self) -> Duration {
+        Duration::from_secs(self.repo_refill_secs)
+    }
+}
+
+/// Network where verified programs can be deployed
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Name used to refer to the network in verification requests
+    pub name: String,
+    /// RPC URLs of the network nodes, tried in order until one is available
+    pub rpc_urls: Vec<String>,
+    /// Expected genesis hash of the network. Nodes of other networks are rejected
+    pub genesis_hash: Option<String>,
+}
+
+impl NetworkConfig {
+    fn validate(&self) -> Result<()> {
+        let is_valid_name = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'));
+        if !is_valid_name {
+            bail!("Invalid network name {:?}", self.name);
+        }
+        if self.rpc_urls.is_empty() {
+            bail!("No RPC URLs are set for network {}", self.name);
+        }
+        if let Some(genesis_hash) = &self.genesis_hash {
+            let hash = genesis_hash.strip_prefix("0x").unwrap_or(genesis_hash);
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("Invalid genesis hash of network {}", self.name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn override_from_env<T>(value: &mut T, name: &str) -> Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(env_value) = env::var(name) {
+        *value = env_value
+            .parse()
+            .map_err(|e| anyhow!("Invalid value of {name}: {e}"))?;
+    }
+
+    Ok(())
+}
+
+fn override_option_from_env<T>(value: &mut Option<T>, name: &str) -> Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(env_value) = env::var(name) {
+        *value = Some(
+            env_value
+                .parse()
+                .map_err(|e| anyhow!("Invalid value of {name}: {e}"))?,
+        );
+    }
+
+    Ok(())
+}
+
+impl Config {
+    /// Loads the config file, applies environment overrides and validates the result
+    pub fn load() -> Result<Self> {
+        let path = env::var("CONFIG_PATH").ok();
+
+        let mut config = match fs::read_to_string(path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH)) {
+            Ok(content) => toml::from_str(&content)?,
+            // The file is optional unless its path is set explicitly
+            Err(_) if path.is_none() => Config::default(),
+            Err(error) => bail!("Failed to read config file {}. {error}", path.unwrap()),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        override_from_env(&mut self.server.bind_addr, "BIND_ADDR")?;
+        override_option_from_env(&mut self.server.admin_token, "ADMIN_TOKEN")?;
+        override_from_env(
+            &mut self.server.programs_cache_ttl_secs,
+            "PROGRAMS_CACHE_TTL_SECS",
+        )?;
+
+        override_from_env(&mut self.auth.anonymous_read, "AUTH_ANONYMOUS_READ")?;
+        override_from_env(&mut self.auth.anonymous_submit, "AUTH_ANONYMOUS_SUBMIT")?;
+
+        override_from_env(&mut self.database.url, "DATABASE_URL")?;
+        override_from_env(&mut self.database.pool_size, "DB_POOL_SIZE")?;
+
+        override_from_env(
+            &mut self.processor.max_verifs_in_progress,
+            "MAX_VERIFS_IN_PROGRESS",
+        )?;
+        override_from_env(
+            &mut self.processor.check_interval_secs,
+            "CHECK_INTERVAL_SECS",
+        )?;
+        override_from_env(&mut self.processor.max_attempts, "MAX_ATTEMPTS")?;
+        override_from_env(&mut self.processor.retry_base_secs, "RETRY_BASE_SECS")?;
+
+        override_from_env(&mut self.builder.builds_dir, "PATH_TO_BUILDS")?;
+        override_from_env(&mut self.builder.logs_dir, "LOGS_DIR")?;
+        override_from_env(&mut self.builder.artifacts_dir, "ARTIFACTS_DIR")?;
+        override_from_env(&mut self.builder.image_name, "IMAGE_NAME")?;
+        override_from_env(&mut self.builder.base_image, "BASE_IMAGE")?;
+        override_from_env(&mut self.builder.memory, "BUILDER_MEMORY")?;
+        override_from_env(&mut self.builder.nano_cpus, "BUILDER_NANO_CPUS")?;
+        override_from_env(&mut self.builder.pids_limit, "BUILDER_PIDS_LIMIT")?;
+        override_option_from_env(&mut self.builder.storage_size, "BUILDER_STORAGE_SIZE")?;
+        override_from_env(&mut self.builder.timeout_minutes, "BUILD_TIMEOUT_MINUTES")?;
+        override_from_env(&mut self.builder.files_ttl_days, "BUILD_FILES_TTL_DAYS")?;
+
+        override_from_env(&mut self.rate_limit.ip_burst, "RATE_LIMIT_IP_BURST")?;
+        override_from_env(
+            &mut self.rate_limit.ip_refill_secs,
+            "RATE_LIMIT_IP_REFILL_SECS",
+        )?;
+        override_from_env(&mut self.rate_limit.key_burst, "RATE_LIMIT_KEY_BURST")?;
+        override_from_env(
+            &mut self.rate_limit.key_refill_secs,
+            "RATE_LIMIT_KEY_REFILL_SECS",
+        )?;
+        override_from_env(&mut self.rate_limit.repo_burst, "RATE_LIMIT_REPO_BURST")?;
+        override_from_env(
+            &mut self.rate_limit.repo_refill_secs,
+            "RATE_LIMIT_REPO_REFILL_SECS",
+        )?;
+        override_from_env(
+            &mut self.rate_limit.max_pending_per_repo,
+            "MAX_PENDING_PER_REPO",
+        )?;
+        override_from_env(
+            &mut self.rate_limit.max_pending_per_code,
+            "MAX_PENDING_PER_CODE",
+        )?;
+        override_from_env(
+            &mut self.rate_limit.trust_forwarded_for,
+            "RATE_LIMIT_TRUST_FORWARDED_FOR",
+        )?;
+
+        override_option_from_env(&mut self.webhooks.secret, "WEBHOOK_SECRET")?;
+        override_from_env(&mut self.webhooks.max_attempts, "WEBHOOK_MAX_ATTEMPTS")?;
+        override_from_env(
+            &mut self.webhooks.retry_base_secs,
+            "WEBHOOK_RETRY_BASE_SECS",
+        )?;
+        override_from_env(&mut self.webhooks.timeout_secs, "WEBHOOK_TIMEOUT_SECS")?;
+
+        // Kept for compatibility with deployments configured before the network registry
+        self.override_network_from_env("vara_mainnet", "MAINNET_URL");
+        self.override_network_from_env("vara_testnet", "TESTNET_URL");
+
+        Ok(())
+    }
+
+    /// Replaces RPC URLs of the network with the one from the environment variable,
+    /// adding the network if it's not defined in the config file
+    fn override_network_from_env(&mut self, network: &str, name: &str) {
+        let Ok(url) = env::var(name) else {
+            return;
+        };
+
+        match self.networks.iter_mut().find(|n| n.name == network) {
+            Some(n) => n.rpc_urls = vec![url],
+            None => self.networks.push(NetworkConfig {
+                name: network.to_string(),
+                rpc_urls: vec![url],
+                genesis_hash: None,
+            }),
+        }
+    }
+
+    pub fn network(&self, name: &str) -> Option<&NetworkConfig> {
+        self.networks.iter().find(|n| n.name == name)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.database.url.is_empty() {
+            bail!("Database URL is not set");
+        }
+        if self.database.pool_size == 0 {
+            bail!("Database pool size must be positive");
+        }
+        if self.processor.max_verifs_in_progress <= 0 {
+            bail!("Maximum number of verifications in progress must be positive");
+        }
+        if self.processor.check_interval_secs == 0 {
+            bail!("Check interval must be positive");
+        }
+        if self.processor.max_attempts <= 0 || self.processor.retry_base_secs == 0 {
+            bail!("Verification retry settings must be positive");
+        }
+        if !self.builder.builds_dir.is_absolute()
+            || !self.builder.logs_dir.is_absolute()
+            || !self.builder.artifacts_dir.is_absolute()
+        {
+            bail!("Builds, logs and artifacts directories must be absolute paths");
+        }
+        if self.builder.image_name.is_empty() || self.builder.base_image.is_empty() {
+            bail!("Image names must not be empty");
+        }
+        if self.builder.memory <= 0 || self.builder.nano_cpus <= 0 || self.builder.pids_limit <= 0 {
+            bail!("Builder resource limits must be positive");
+        }
+        if self.builder.timeout_minutes == 0 {
+            bail!("Build timeout must be positive");
+        }
+        if self.builder.files_ttl_days == 0 {
+            bail!("Build files TTL must be positive");
+        }
+        if self.rate_limit.ip_burst == 0
+            || self.rate_limit.ip_refill_secs == 0
+            || self.rate_limit.key_burst == 0
+            || self.rate_limit.key_refill_secs == 0
+            || self.rate_limit.repo_burst == 0
+            || self.rate_limit.repo_refill_secs == 0
+        {
+            bail!("Rate limits must be positive");
+        }
+        if self.rate_limit.max_pending_per_repo <= 0 || self.rate_limit.max_pending_per_code <= 0 {
+            bail!("Maximum numbers of pending verifications must be positive");
+        }
+        if self.webhooks.max_attempts <= 0 || self.webhooks.timeout_secs == 0 {
+            bail!("Webhook attempts and timeout must be positive");
+        }
+        if self.networks.is_empty() {
+            bail!("No networks are configured");
+        }
+        for (i, network) in self.networks.iter().enumerate() {
+            network.validate()?;
+            if self.networks[..i].iter().any(|n| n.name == network.name) {
+                bail!("Network {} is defined more than once", network.name);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,40 @@
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+pub const DEFAULT_BIND_ADDR: &str = "0.0.0.0:3000";
+
+pub const DEFAULT_POOL_SIZE: u32 = 10;
+pub const DEFAULT_PROGRAMS_CACHE_TTL_SECS: u64 = 3600;
+
+pub const DEFAULT_MAX_VERIFS_IN_PROGRESS: i64 = 10;
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 3;
+pub const DEFAULT_RETRY_BASE_SECS: u64 = 60;
+
 pub const PATH_TO_BUILDS: &str = "/var/tmp/builds";
 
 pub const IMAGE_NAME: &str = "verifier";
-pub const AVAILABLE_VERSIONS: [&str; 2] = ["0.8.0", "0.8.1"];
+
+pub const DEFAULT_BASE_IMAGE: &str = "ghcr.io/gear-tech/sails-program-builder";
 
 pub const LOGS_DIR: &str = "/tmp/build_logs";
+
+pub const ARTIFACTS_DIR: &str = "/var/tmp/artifacts";
+
+pub const DEFAULT_BUILDER_MEMORY: i64 = 8 * 1024 * 1024 * 1024;
+pub const DEFAULT_BUILDER_NANO_CPUS: i64 = 2_000_000_000;
+pub const DEFAULT_BUILDER_PIDS_LIMIT: i64 = 1024;
+pub const DEFAULT_BUILD_TIMEOUT_MINUTES: u64 = 30;
+pub const DEFAULT_FILES_TTL_DAYS: u64 = 30;
+
+pub const DEFAULT_RATE_LIMIT_IP_BURST: u32 = 10;
+pub const DEFAULT_RATE_LIMIT_IP_REFILL_SECS: u64 = 60;
+pub const DEFAULT_RATE_LIMIT_REPO_BURST: u32 = 5;
+pub const DEFAULT_RATE_LIMIT_REPO_REFILL_SECS: u64 = 600;
+pub const DEFAULT_RATE_LIMIT_KEY_BURST: u32 = 100;
+pub const DEFAULT_RATE_LIMIT_KEY_REFILL_SECS: u64 = 6;
+pub const DEFAULT_MAX_PENDING_PER_REPO: i64 = 3;
+pub const DEFAULT_MAX_PENDING_PER_CODE: i64 = 1;
+
+pub const DEFAULT_WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+pub const DEFAULT_WEBHOOK_RETRY_BASE_SECS: u64 = 10;
+pub const DEFAULT_WEBHOOK_TIMEOUT_SECS: u64 = 10;
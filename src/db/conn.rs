@@ -1,16 +1,14 @@
+use crate::config::DatabaseConfig;
 use diesel::{
     r2d2::{ConnectionManager, Pool},
     PgConnection,
 };
-use std::env;
 
-pub fn get_connection_pool() -> Pool<ConnectionManager<PgConnection>> {
-    let url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-
-    let manager = ConnectionManager::<PgConnection>::new(url);
+pub fn get_connection_pool(config: &DatabaseConfig) -> Pool<ConnectionManager<PgConnection>> {
+    let manager = ConnectionManager::<PgConnection>::new(&config.url);
 
     Pool::builder()
-        .max_size(10)
+        .max_size(config.pool_size)
         .build(manager)
         .expect("Failed to create pool")
 }
@@ -3,4 +3,8 @@ mod model;
 pub mod schema;
 
 pub use conn::get_connection_pool;
-pub use model::{Code, Idl, Network, Verification, VerificationStatus};
+pub use model::{
+    ApiKey, ApiScope, BuildResult, Code, CodeComparison, CodePrograms, FailureKind, Idl,
+    ImageStatus, Program, SailsVersion, Verification, VerificationFilter, VerificationStatus,
+    WebhookDelivery,
+};
@@ -1,17 +1,24 @@
-use super::schema::{self, code::dsl as code_dsl, verification::dsl as verif_dsl};
+use super::schema::{
+    self, api_key::dsl as api_key_dsl, code::dsl as code_dsl,
+    code_programs::dsl as code_programs_dsl, program::dsl as program_dsl,
+    sails_version::dsl as version_dsl, verification::dsl as verif_dsl,
+    webhook_delivery::dsl as webhook_dsl,
+};
 use diesel::{
     deserialize::{FromSql, FromSqlRow},
     expression::AsExpression,
+    expression_methods::PgExpressionMethods,
     pg::PgValue,
-    prelude::{Insertable, Queryable},
+    prelude::{AsChangeset, Insertable, Queryable},
     serialize::{IsNull, ToSql},
-    ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable, SelectableHelper,
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    RunQueryDsl, Selectable, SelectableHelper,
 };
-use serde::Serialize;
-use std::{io::Write, time::SystemTime};
+use serde::{Deserialize, Serialize};
+use std::{io::Write, str::FromStr, time::SystemTime};
 use utoipa::ToSchema;
 
-#[derive(Queryable, Selectable, Insertable, Serialize, ToSchema)]
+#[derive(Queryable, Selectable, Insertable, Serialize, ToSchema, Clone)]
 #[diesel(table_name = schema::code)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Code {
@@ -19,6 +26,7 @@ pub struct Code {
     pub idl_hash: Option<String>,
     pub name: String,
     pub repo_link: String,
+    pub commit_sha: Option<String>,
 }
 
 impl Code {
@@ -28,12 +36,14 @@ impl Code {
         repo_link: String,
         name: String,
         idl_hash: Option<String>,
+        commit_sha: Option<String>,
     ) -> Result<Code, diesel::result::Error> {
         let code = Code {
             id,
             idl_hash,
             name,
             repo_link,
+            commit_sha,
         };
 
         diesel::insert_into(schema::code::table)
@@ -89,6 +99,361 @@ impl Idl {
     }
 }
 
+/// Program whose code ID was resolved from the chain
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = schema::program)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Program {
+    pub id: String,
+    pub code_id: String,
+    pub network: String,
+    pub created_at: SystemTime,
+}
+
+impl Program {
+    pub fn save(
+        conn: &mut PgConnection,
+        id: &str,
+        code_id: &str,
+        network: &str,
+    ) -> Result<(), diesel::result::Error> {
+        let program = Program {
+            id: id.to_string(),
+            code_id: code_id.to_string(),
+            network: network.to_string(),
+            created_at: SystemTime::now(),
+        };
+
+        diesel::insert_into(schema::program::table)
+            .values(&program)
+            .on_conflict((program_dsl::id, program_dsl::network))
+            .do_update()
+            .set(program_dsl::code_id.eq(code_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Program of the network, or the most recently resolved one if the network isn't set
+    pub fn get(conn: &mut PgConnection, id: &str, network: Option<&str>) -> Option<Program> {
+        let mut query = program_dsl::program
+            .filter(program_dsl::id.eq(id))
+            .into_boxed();
+
+        if let Some(network) = network {
+            query = query.filter(program_dsl::network.eq(network));
+        }
+
+        query
+            .order_by(program_dsl::created_at.desc())
+            .first(conn)
+            .ok()
+    }
+}
+
+/// Cached list of the programs running the code
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = schema::code_programs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CodePrograms {
+    pub code_id: String,
+    pub network: String,
+    pub program_ids: Vec<String>,
+    pub updated_at: SystemTime,
+}
+
+impl CodePrograms {
+    pub fn save(
+        conn: &mut PgConnection,
+        code_id: &str,
+        network: &str,
+        program_ids: Vec<String>,
+    ) -> Result<CodePrograms, diesel::result::Error> {
+        let programs = CodePrograms {
+            code_id: code_id.to_string(),
+            network: network.to_string(),
+            program_ids,
+            updated_at: SystemTime::now(),
+        };
+
+        diesel::insert_into(schema::code_programs::table)
+            .values(&programs)
+            .on_conflict((code_programs_dsl::code_id, code_programs_dsl::network))
+            .do_update()
+            .set((
+                code_programs_dsl::program_ids.eq(&programs.program_ids),
+                code_programs_dsl::updated_at.eq(programs.updated_at),
+            ))
+            .execute(conn)?;
+
+        Ok(programs)
+    }
+
+    pub fn get(conn: &mut PgConnection, code_id: &str, network: &str) -> Option<CodePrograms> {
+        code_programs_dsl::code_programs
+            .find((code_id, network))
+            .first(conn)
+            .ok()
+    }
+}
+
+/// Permission granted to an API key
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Read verifications, codes and other public data
+    Read,
+    /// Submit verification requests
+    Submit,
+    /// Manage Sails versions and API keys
+    Admin,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::Read => "read",
+            ApiScope::Submit => "submit",
+            ApiScope::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(ApiScope::Read),
+            "submit" => Ok(ApiScope::Submit),
+            "admin" => Ok(ApiScope::Admin),
+            _ => Err(anyhow::anyhow!("Unrecognized API scope {s}")),
+        }
+    }
+}
+
+/// API key of a client. Only the hash of the key is stored
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = schema::api_key)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: SystemTime,
+    pub revoked_at: Option<SystemTime>,
+}
+
+impl ApiKey {
+    pub fn save(
+        conn: &mut PgConnection,
+        id: &str,
+        name: &str,
+        key_hash: &str,
+        scopes: &[ApiScope],
+    ) -> Result<ApiKey, diesel::result::Error> {
+        let api_key = ApiKey {
+            id: id.to_string(),
+            name: name.to_string(),
+            key_hash: key_hash.to_string(),
+            scopes: scopes.iter().map(|s| s.as_str().to_string()).collect(),
+            created_at: SystemTime::now(),
+            revoked_at: None,
+        };
+
+        diesel::insert_into(schema::api_key::table)
+            .values(&api_key)
+            .returning(ApiKey::as_returning())
+            .get_result(conn)
+    }
+
+    /// Returns the key with the hash, unless it's revoked
+    pub fn get_active(conn: &mut PgConnection, key_hash: &str) -> Option<ApiKey> {
+        api_key_dsl::api_key
+            .filter(api_key_dsl::key_hash.eq(key_hash))
+            .filter(api_key_dsl::revoked_at.is_null())
+            .first(conn)
+            .ok()
+    }
+
+    pub fn revoke(conn: &mut PgConnection, id: &str) -> Result<usize, diesel::result::Error> {
+        diesel::update(
+            api_key_dsl::api_key
+                .find(id)
+                .filter(api_key_dsl::revoked_at.is_null()),
+        )
+        .set(api_key_dsl::revoked_at.eq(SystemTime::now()))
+        .execute(conn)
+    }
+
+    /// Scopes granted to the key. Unrecognized scopes are ignored
+    pub fn scopes(&self) -> Vec<ApiScope> {
+        self.scopes.iter().filter_map(|s| s.parse().ok()).collect()
+    }
+}
+
+/// Delivery of the callback of a finished verification
+#[derive(Queryable, Selectable, Insertable, Debug)]
+#[diesel(table_name = schema::webhook_delivery)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDelivery {
+    pub verification_id: String,
+    pub url: String,
+    pub attempts: i32,
+    /// Time of the next attempt, `None` once delivered or given up
+    pub next_attempt_at: Option<SystemTime>,
+    pub delivered_at: Option<SystemTime>,
+    pub last_error: Option<String>,
+    pub created_at: SystemTime,
+}
+
+impl WebhookDelivery {
+    /// Schedules the delivery if the verification has a callback URL.
+    /// Repeated final statuses restart the delivery
+    pub fn schedule(
+        conn: &mut PgConnection,
+        verification_id: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let callback_url: Option<String> = verif_dsl::verification
+            .find(verification_id)
+            .select(verif_dsl::callback_url)
+            .first(conn)?;
+
+        let Some(url) = callback_url else {
+            return Ok(0);
+        };
+
+        let now = SystemTime::now();
+        let delivery = WebhookDelivery {
+            verification_id: verification_id.to_string(),
+            url,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            delivered_at: None,
+            last_error: None,
+            created_at: now,
+        };
+
+        diesel::insert_into(schema::webhook_delivery::table)
+            .values(&delivery)
+            .on_conflict(webhook_dsl::verification_id)
+            .do_update()
+            .set((
+                webhook_dsl::attempts.eq(0),
+                webhook_dsl::next_attempt_at.eq(now),
+                webhook_dsl::delivered_at.eq(None::<SystemTime>),
+                webhook_dsl::last_error.eq(None::<String>),
+            ))
+            .execute(conn)
+    }
+
+    /// Returns deliveries whose next attempt is due, along with their verifications
+    pub fn get_due(
+        conn: &mut PgConnection,
+        count: i64,
+    ) -> Result<Vec<(WebhookDelivery, Verification)>, diesel::result::Error> {
+        webhook_dsl::webhook_delivery
+            .inner_join(verif_dsl::verification)
+            .filter(webhook_dsl::next_attempt_at.le(SystemTime::now()))
+            .order_by(webhook_dsl::next_attempt_at)
+            .limit(count)
+            .select((WebhookDelivery::as_select(), Verification::as_select()))
+            .load(conn)
+    }
+
+    /// Records the result of an attempt. `next_attempt_at` is `None` if the delivery is finished
+    pub fn record_attempt(
+        conn: &mut PgConnection,
+        verification_id: &str,
+        error: Option<String>,
+        next_attempt_at: Option<SystemTime>,
+    ) -> Result<usize, diesel::result::Error> {
+        let delivered_at = error.is_none().then(SystemTime::now);
+
+        diesel::update(webhook_dsl::webhook_delivery.find(verification_id))
+            .set((
+                webhook_dsl::attempts.eq(webhook_dsl::attempts + 1),
+                webhook_dsl::next_attempt_at.eq(next_attempt_at),
+                webhook_dsl::delivered_at.eq(delivered_at),
+                webhook_dsl::last_error.eq(error),
+            ))
+            .execute(conn)
+    }
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, ToSchema)]
+#[diesel(table_name = schema::sails_version)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SailsVersion {
+    pub version: String,
+    #[serde(skip)]
+    pub created_at: SystemTime,
+    pub status: ImageStatus,
+}
+
+impl SailsVersion {
+    pub fn save(conn: &mut PgConnection, version: &str) -> Result<(), diesel::result::Error> {
+        let version = SailsVersion {
+            version: version.to_string(),
+            created_at: SystemTime::now(),
+            status: ImageStatus::Building,
+        };
+
+        diesel::insert_into(schema::sails_version::table)
+            .values(&version)
+            .on_conflict(version_dsl::version)
+            .do_update()
+            .set(version_dsl::status.eq(ImageStatus::Building))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn get(conn: &mut PgConnection, version: &str) -> Option<SailsVersion> {
+        version_dsl::sails_version.find(version).first(conn).ok()
+    }
+
+    /// Blocks status updates of the version until the end of the transaction, so verifications
+    /// saved in it can't miss the failure of the version image
+    pub fn lock_status(
+        conn: &mut PgConnection,
+        version: &str,
+    ) -> Result<(), diesel::result::Error> {
+        version_dsl::sails_version
+            .find(version)
+            .select(version_dsl::version)
+            .for_share()
+            .first::<String>(conn)
+            .optional()?;
+
+        Ok(())
+    }
+
+    pub fn get_all(conn: &mut PgConnection) -> Result<Vec<SailsVersion>, diesel::result::Error> {
+        version_dsl::sails_version
+            .order_by(version_dsl::created_at)
+            .load::<SailsVersion>(conn)
+    }
+
+    pub fn update_status(
+        conn: &mut PgConnection,
+        version: &str,
+        status: ImageStatus,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(version_dsl::sails_version.find(version))
+            .set(version_dsl::status.eq(status))
+            .execute(conn)
+    }
+
+    /// Images are rebuilt on every start, so none of them are ready until then
+    pub fn reset_status(conn: &mut PgConnection) -> Result<usize, diesel::result::Error> {
+        diesel::update(version_dsl::sails_version)
+            .set(version_dsl::status.eq(ImageStatus::Building))
+            .execute(conn)
+    }
+}
+
 #[derive(Queryable, Selectable, Insertable)]
 #[diesel(table_name = schema::verification)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -96,25 +461,151 @@ impl Idl {
 pub struct Verification {
     pub id: String,
     pub repo_link: String,
-    pub code_id: String,
+    /// ID of the code, resolved by the processor if the program ID was requested
+    pub code_id: Option<String>,
     pub project_name: Option<String>,
     pub manifest_path: Option<String>,
     pub build_idl: bool,
     pub version: String,
     pub status: VerificationStatus,
-    pub network: Network,
+    pub network: String,
     pub failed_reason: Option<String>,
     pub created_at: SystemTime,
     pub base_path: Option<String>,
+    pub git_ref: Option<String>,
+    pub commit_sha: Option<String>,
+    pub built_code_len: Option<i32>,
+    pub built_code_hash: Option<String>,
+    pub onchain_code_len: Option<i32>,
+    pub onchain_code_hash: Option<String>,
+    pub diff_report: Option<serde_json::Value>,
+    pub calculated_code_id: Option<String>,
+    pub wasm_name: Option<String>,
+    pub artifact_sizes: Option<serde_json::Value>,
+    pub program_id: Option<String>,
+    /// URL notified when the verification reaches a final status
+    pub callback_url: Option<String>,
+    /// Key supplied by the client, requests with the same key return this verification
+    pub idempotency_key: Option<String>,
+    /// Number of times the verification was requeued after a transient failure
+    pub attempts: i32,
+    /// Time the requeued verification is processed at
+    pub next_attempt_at: Option<SystemTime>,
+    /// Category of the failure, `failed_reason` describes it
+    pub failure_kind: Option<FailureKind>,
+    /// Caller the idempotency key belongs to
+    pub idempotency_scope: Option<String>,
+    /// Hash of the request with the idempotency key, repeated requests must match it
+    pub request_hash: Option<String>,
+    /// ID of the API key the verification was requested with
+    pub submitter: Option<String>,
+    /// Hash of the token returned to the client, allowing it to cancel or retry the verification
+    pub cancel_token_hash: Option<String>,
+}
+
+/// Filters of listed verifications, unset ones match any verification
+#[derive(Default, Debug)]
+pub struct VerificationFilter {
+    pub code_id: Option<String>,
+    pub repo_link: Option<String>,
+    pub status: Option<VerificationStatus>,
+    pub network: Option<String>,
+    pub version: Option<String>,
+    /// Inclusive lower bound of the creation time
+    pub created_after: Option<SystemTime>,
+    /// Exclusive upper bound of the creation time
+    pub created_before: Option<SystemTime>,
+}
+
+/// Outputs of a successful build
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = schema::verification)]
+pub struct BuildResult {
+    pub calculated_code_id: Option<String>,
+    pub wasm_name: Option<String>,
+    /// Sizes of the produced files in bytes, keyed by file name
+    pub artifact_sizes: Option<serde_json::Value>,
+}
+
+/// Lengths and hashes of the built code and of the original code fetched from the chain
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = schema::verification)]
+pub struct CodeComparison {
+    pub built_code_len: Option<i32>,
+    pub built_code_hash: Option<String>,
+    pub onchain_code_len: Option<i32>,
+    pub onchain_code_hash: Option<String>,
+    /// Comparison of the codes, made only if they differ
+    pub diff_report: Option<serde_json::Value>,
 }
 
 impl Verification {
-    pub fn save(conn: &mut PgConnection, verif: Verification) -> Self {
+    pub fn save(
+        conn: &mut PgConnection,
+        verif: Verification,
+    ) -> Result<Verification, diesel::result::Error> {
         diesel::insert_into(schema::verification::table)
             .values(&verif)
             .returning(Verification::as_returning())
             .get_result(conn)
-            .expect("Error saving Verification")
+    }
+
+    pub fn get_by_idempotency_key(
+        conn: &mut PgConnection,
+        scope: &str,
+        key: &str,
+    ) -> Option<Verification> {
+        verif_dsl::verification
+            .filter(verif_dsl::idempotency_scope.eq(scope))
+            .filter(verif_dsl::idempotency_key.eq(key))
+            .first(conn)
+            .ok()
+    }
+
+    /// Holds new verifications of the repository until the end of the transaction,
+    /// so concurrent equivalent requests are checked and saved one at a time
+    pub fn lock_repo(
+        conn: &mut PgConnection,
+        repo_link: &str,
+    ) -> Result<(), diesel::result::Error> {
+        diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind::<diesel::sql_types::Text, _>(repo_link)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Returns the latest pending, in progress or verified verification of the same sources
+    /// as the requested one
+    pub fn find_equivalent(conn: &mut PgConnection, verif: &Verification) -> Option<Verification> {
+        let mut query = verif_dsl::verification
+            .filter(verif_dsl::repo_link.eq(&verif.repo_link))
+            .into_boxed();
+
+        // Code ID of a program verification is set once the program is resolved
+        query = match &verif.program_id {
+            Some(program_id) => query.filter(verif_dsl::program_id.eq(program_id)),
+            None => query
+                .filter(verif_dsl::code_id.is_not_distinct_from(&verif.code_id))
+                .filter(verif_dsl::program_id.is_null()),
+        };
+
+        query
+            .filter(verif_dsl::version.eq(&verif.version))
+            .filter(verif_dsl::project_name.is_not_distinct_from(&verif.project_name))
+            .filter(verif_dsl::manifest_path.is_not_distinct_from(&verif.manifest_path))
+            .filter(verif_dsl::base_path.is_not_distinct_from(&verif.base_path))
+            .filter(verif_dsl::git_ref.is_not_distinct_from(&verif.git_ref))
+            .filter(verif_dsl::network.eq(&verif.network))
+            .filter(verif_dsl::build_idl.eq(verif.build_idl))
+            .filter(verif_dsl::status.eq_any([
+                VerificationStatus::Pending,
+                VerificationStatus::InProgress,
+                VerificationStatus::Verified,
+            ]))
+            .order_by(verif_dsl::created_at.desc())
+            .first(conn)
+            .ok()
     }
 
     pub fn get(conn: &mut PgConnection, id: &str) -> Option<Verification> {
@@ -134,30 +625,356 @@ impl Verification {
             .is_ok()
     }
 
+    /// Whether the verification was cancelled, e.g. while its build was being prepared
+    pub fn is_cancelled(conn: &mut PgConnection, id: &str) -> bool {
+        verif_dsl::verification
+            .find(id)
+            .filter(verif_dsl::status.eq(VerificationStatus::Cancelled))
+            .first::<Verification>(conn)
+            .is_ok()
+    }
+
+    /// Number of unfinished verifications of the repository
+    pub fn count_unfinished_by_repo(
+        conn: &mut PgConnection,
+        repo_link: &str,
+    ) -> Result<i64, diesel::result::Error> {
+        verif_dsl::verification
+            .filter(verif_dsl::repo_link.eq(repo_link))
+            .filter(
+                verif_dsl::status
+                    .eq_any([VerificationStatus::Pending, VerificationStatus::InProgress]),
+            )
+            .count()
+            .get_result(conn)
+    }
+
+    /// Number of unfinished verifications of the code or of the program
+    pub fn count_unfinished_by_code(
+        conn: &mut PgConnection,
+        code_id: Option<&str>,
+        program_id: Option<&str>,
+    ) -> Result<i64, diesel::result::Error> {
+        verif_dsl::verification
+            .filter(
+                verif_dsl::code_id
+                    .eq(code_id)
+                    .or(verif_dsl::program_id.eq(program_id)),
+            )
+            .filter(
+                verif_dsl::status
+                    .eq_any([VerificationStatus::Pending, VerificationStatus::InProgress]),
+            )
+            .count()
+            .get_result(conn)
+    }
+
     pub fn update(
         conn: &mut PgConnection,
         id: &str,
         status: VerificationStatus,
+    ) -> Result<usize, anyhow::Error> {
+        Self::set_status(conn, id, status, None, None)
+    }
+
+    /// Fails the verification with the message shown to users, which must not contain error details
+    pub fn fail(
+        conn: &mut PgConnection,
+        id: &str,
+        kind: FailureKind,
+        reason: String,
+    ) -> Result<usize, anyhow::Error> {
+        Self::set_status(
+            conn,
+            id,
+            VerificationStatus::Failed,
+            Some(kind),
+            Some(reason),
+        )
+    }
+
+    fn set_status(
+        conn: &mut PgConnection,
+        id: &str,
+        status: VerificationStatus,
+        kind: Option<FailureKind>,
         reason: Option<String>,
+    ) -> Result<usize, anyhow::Error> {
+        let is_final = status.is_final();
+
+        // Cancelled verifications are left as they are, even if their build is still finishing
+        let updated = diesel::update(
+            verif_dsl::verification
+                .find(id)
+                .filter(verif_dsl::status.ne(VerificationStatus::Cancelled)),
+        )
+        .set((
+            verif_dsl::status.eq(status),
+            verif_dsl::failure_kind.eq(kind),
+            verif_dsl::failed_reason.eq(reason),
+        ))
+        .execute(conn)
+        .map_err(|e| anyhow::anyhow!("Failed to update verification {}. Error: {:?}", id, e))?;
+
+        if is_final && updated > 0 {
+            WebhookDelivery::schedule(conn, id).map_err(|e| {
+                anyhow::anyhow!("Failed to schedule callback of {}. Error: {:?}", id, e)
+            })?;
+        }
+
+        Ok(updated)
+    }
+
+    /// Cancels the verification if it is pending or in progress.
+    /// Returns `false` if the verification is already finished
+    pub fn cancel(conn: &mut PgConnection, id: &str) -> Result<bool, anyhow::Error> {
+        let cancelled = diesel::update(verif_dsl::verification.find(id).filter(
+            verif_dsl::status.eq_any([VerificationStatus::Pending, VerificationStatus::InProgress]),
+        ))
+        .set((
+            verif_dsl::status.eq(VerificationStatus::Cancelled),
+            verif_dsl::failure_kind.eq(None::<FailureKind>),
+            verif_dsl::failed_reason.eq(None::<String>),
+        ))
+        .execute(conn)
+        .map_err(|e| anyhow::anyhow!("Failed to cancel verification {}. Error: {:?}", id, e))?;
+
+        if cancelled == 0 {
+            return Ok(false);
+        }
+
+        WebhookDelivery::schedule(conn, id).map_err(|e| {
+            anyhow::anyhow!("Failed to schedule callback of {}. Error: {:?}", id, e)
+        })?;
+
+        Ok(true)
+    }
+
+    /// Returns the verification to the queue after a transient failure,
+    /// keeping the reason until it's processed again
+    pub fn requeue(
+        conn: &mut PgConnection,
+        id: &str,
+        kind: FailureKind,
+        reason: String,
+        next_attempt_at: SystemTime,
+    ) -> Result<usize, anyhow::Error> {
+        diesel::update(
+            verif_dsl::verification
+                .find(id)
+                .filter(verif_dsl::status.ne(VerificationStatus::Cancelled)),
+        )
+        .set((
+            verif_dsl::status.eq(VerificationStatus::Pending),
+            verif_dsl::failure_kind.eq(kind),
+            verif_dsl::failed_reason.eq(reason),
+            verif_dsl::attempts.eq(verif_dsl::attempts + 1),
+            verif_dsl::next_attempt_at.eq(next_attempt_at),
+        ))
+        .execute(conn)
+        .map_err(|e| anyhow::anyhow!("Failed to requeue verification {}. Error: {:?}", id, e))
+    }
+
+    /// Queues the failed verification again, discarding the results of its last build.
+    /// Returns `false` if the verification hasn't failed
+    pub fn retry(conn: &mut PgConnection, id: &str) -> Result<bool, anyhow::Error> {
+        diesel::update(
+            verif_dsl::verification
+                .find(id)
+                .filter(verif_dsl::status.eq(VerificationStatus::Failed)),
+        )
+        .set((
+            (
+                verif_dsl::status.eq(VerificationStatus::Pending),
+                verif_dsl::failure_kind.eq(None::<FailureKind>),
+                verif_dsl::failed_reason.eq(None::<String>),
+                verif_dsl::attempts.eq(0),
+                verif_dsl::next_attempt_at.eq(None::<SystemTime>),
+                verif_dsl::commit_sha.eq(None::<String>),
+            ),
+            (
+                verif_dsl::built_code_len.eq(None::<i32>),
+                verif_dsl::built_code_hash.eq(None::<String>),
+                verif_dsl::onchain_code_len.eq(None::<i32>),
+                verif_dsl::onchain_code_hash.eq(None::<String>),
+                verif_dsl::diff_report.eq(None::<serde_json::Value>),
+                verif_dsl::calculated_code_id.eq(None::<String>),
+                verif_dsl::wasm_name.eq(None::<String>),
+                verif_dsl::artifact_sizes.eq(None::<serde_json::Value>),
+            ),
+        ))
+        .execute(conn)
+        .map(|updated| updated > 0)
+        .map_err(|e| anyhow::anyhow!("Failed to retry verification {}. Error: {:?}", id, e))
+    }
+
+    pub fn set_commit_sha(
+        conn: &mut PgConnection,
+        id: &str,
+        commit_sha: &str,
     ) -> Result<usize, anyhow::Error> {
         diesel::update(verif_dsl::verification.find(id))
-            .set((
-                verif_dsl::status.eq(status),
-                verif_dsl::failed_reason.eq(reason),
-            ))
+            .set(verif_dsl::commit_sha.eq(commit_sha))
             .execute(conn)
             .map_err(|e| anyhow::anyhow!("Failed to update verification {}. Error: {:?}", id, e))
     }
 
+    /// Code ID of the verification. Must be called only after the program ID is resolved
+    pub fn code_id(&self) -> &str {
+        self.code_id
+            .as_deref()
+            .expect("Code ID of the program is not resolved")
+    }
+
+    pub fn set_code_id(
+        conn: &mut PgConnection,
+        id: &str,
+        code_id: &str,
+    ) -> Result<usize, anyhow::Error> {
+        diesel::update(verif_dsl::verification.find(id))
+            .set(verif_dsl::code_id.eq(code_id))
+            .execute(conn)
+            .map_err(|e| anyhow::anyhow!("Failed to update verification {}. Error: {:?}", id, e))
+    }
+
+    pub fn set_build_result(
+        conn: &mut PgConnection,
+        id: &str,
+        result: &BuildResult,
+    ) -> Result<usize, anyhow::Error> {
+        diesel::update(verif_dsl::verification.find(id))
+            .set(result)
+            .execute(conn)
+            .map_err(|e| anyhow::anyhow!("Failed to update verification {}. Error: {:?}", id, e))
+    }
+
+    pub fn set_code_comparison(
+        conn: &mut PgConnection,
+        id: &str,
+        comparison: &CodeComparison,
+    ) -> Result<usize, anyhow::Error> {
+        diesel::update(verif_dsl::verification.find(id))
+            .set(comparison)
+            .execute(conn)
+            .map_err(|e| anyhow::anyhow!("Failed to update verification {}. Error: {:?}", id, e))
+    }
+
+    /// Network of the first successful verification of the code
+    pub fn verified_network(conn: &mut PgConnection, code_id: &str) -> Option<String> {
+        verif_dsl::verification
+            .filter(verif_dsl::code_id.eq(code_id))
+            .filter(verif_dsl::status.eq(VerificationStatus::Verified))
+            .order_by(verif_dsl::created_at)
+            .select(verif_dsl::network)
+            .first(conn)
+            .ok()
+    }
+
+    /// Returns verifications matching the filter, newest first.
+    /// Listing continues after the `(created_at, id)` cursor of the last returned verification
+    pub fn list(
+        conn: &mut PgConnection,
+        filter: VerificationFilter,
+        cursor: Option<(SystemTime, String)>,
+        count: i64,
+    ) -> Result<Vec<Verification>, diesel::result::Error> {
+        let mut query = verif_dsl::verification.into_boxed();
+
+        if let Some(code_id) = filter.code_id {
+            query = query.filter(verif_dsl::code_id.eq(code_id));
+        }
+        if let Some(repo_link) = filter.repo_link {
+            query = query.filter(verif_dsl::repo_link.eq(repo_link));
+        }
+        if let Some(status) = filter.status {
+            query = query.filter(verif_dsl::status.eq(status));
+        }
+        if let Some(network) = filter.network {
+            query = query.filter(verif_dsl::network.eq(network));
+        }
+        if let Some(version) = filter.version {
+            query = query.filter(verif_dsl::version.eq(version));
+        }
+        if let Some(created_after) = filter.created_after {
+            query = query.filter(verif_dsl::created_at.ge(created_after));
+        }
+        if let Some(created_before) = filter.created_before {
+            query = query.filter(verif_dsl::created_at.lt(created_before));
+        }
+        if let Some((created_at, id)) = cursor {
+            query = query.filter(
+                verif_dsl::created_at
+                    .lt(created_at)
+                    .or(verif_dsl::created_at
+                        .eq(created_at)
+                        .and(verif_dsl::id.lt(id))),
+            );
+        }
+
+        query
+            .order_by((verif_dsl::created_at.desc(), verif_dsl::id.desc()))
+            .limit(count)
+            .load(conn)
+    }
+
+    /// Returns the oldest pending verifications, whose verifier images are ready
+    /// and whose retries are due
     pub fn get_pending(conn: &mut PgConnection, count: i64) -> Vec<Verification> {
+        let ready_versions = version_dsl::sails_version
+            .filter(version_dsl::status.eq(ImageStatus::Ready))
+            .select(version_dsl::version);
+
         verif_dsl::verification
             .filter(verif_dsl::status.eq::<VerificationStatus>(VerificationStatus::Pending))
+            .filter(verif_dsl::version.eq_any(ready_versions))
+            .filter(
+                verif_dsl::next_attempt_at
+                    .is_null()
+                    .or(verif_dsl::next_attempt_at.le(SystemTime::now())),
+            )
             .order_by(verif_dsl::created_at)
             .limit(count)
             .load::<Verification>(conn)
             .expect("Error loading pending verifications")
     }
 
+    /// Fails pending verifications of the version, e.g. when its verifier image failed to build.
+    /// Returns the number of failed verifications
+    pub fn fail_pending_of_version(
+        conn: &mut PgConnection,
+        version: &str,
+        kind: FailureKind,
+        reason: &str,
+    ) -> Result<usize, anyhow::Error> {
+        let ids: Vec<String> = diesel::update(
+            verif_dsl::verification
+                .filter(verif_dsl::version.eq(version))
+                .filter(verif_dsl::status.eq(VerificationStatus::Pending)),
+        )
+        .set((
+            verif_dsl::status.eq(VerificationStatus::Failed),
+            verif_dsl::failure_kind.eq(kind),
+            verif_dsl::failed_reason.eq(reason),
+        ))
+        .returning(verif_dsl::id)
+        .get_results(conn)
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to fail verifications of {}. Error: {:?}",
+                version,
+                e
+            )
+        })?;
+
+        for id in &ids {
+            WebhookDelivery::schedule(conn, id).map_err(|e| {
+                anyhow::anyhow!("Failed to schedule callback of {}. Error: {:?}", id, e)
+            })?;
+        }
+
+        Ok(ids.len())
+    }
+
     pub fn reset_in_progress(conn: &mut PgConnection) -> Result<usize, diesel::result::Error> {
         diesel::update(
             verif_dsl::verification.filter(verif_dsl::status.eq(VerificationStatus::InProgress)),
@@ -167,13 +984,41 @@ impl Verification {
     }
 }
 
-#[derive(Debug, AsExpression, FromSqlRow, Serialize, Clone)]
+#[derive(Debug, AsExpression, FromSqlRow, Serialize, Clone, PartialEq)]
 #[diesel(sql_type = schema::sql_types::Verificationstatus)]
 pub enum VerificationStatus {
     Pending,
     InProgress,
     Verified,
     Failed,
+    Cancelled,
+}
+
+impl VerificationStatus {
+    /// Whether the verification is finished and won't be processed again
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            VerificationStatus::Verified
+                | VerificationStatus::Failed
+                | VerificationStatus::Cancelled
+        )
+    }
+}
+
+impl FromStr for VerificationStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(VerificationStatus::Pending),
+            "in_progress" => Ok(VerificationStatus::InProgress),
+            "verified" => Ok(VerificationStatus::Verified),
+            "failed" => Ok(VerificationStatus::Failed),
+            "cancelled" => Ok(VerificationStatus::Cancelled),
+            _ => Err(anyhow::anyhow!("Unrecognized verification status {s}")),
+        }
+    }
 }
 
 impl ToSql<schema::sql_types::Verificationstatus, diesel::pg::Pg> for VerificationStatus {
@@ -186,6 +1031,7 @@ impl ToSql<schema::sql_types::Verificationstatus, diesel::pg::Pg> for Verificati
             VerificationStatus::InProgress => out.write_all(b"in_progress")?,
             VerificationStatus::Verified => out.write_all(b"verified")?,
             VerificationStatus::Failed => out.write_all(b"failed")?,
+            VerificationStatus::Cancelled => out.write_all(b"cancelled")?,
         }
         Ok(IsNull::No)
     }
@@ -198,6 +1044,7 @@ impl FromSql<schema::sql_types::Verificationstatus, diesel::pg::Pg> for Verifica
             b"in_progress" => Ok(VerificationStatus::InProgress),
             b"verified" => Ok(VerificationStatus::Verified),
             b"failed" => Ok(VerificationStatus::Failed),
+            b"cancelled" => Ok(VerificationStatus::Cancelled),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
@@ -210,59 +1057,106 @@ impl From<VerificationStatus> for String {
             VerificationStatus::InProgress => "in_progress".to_string(),
             VerificationStatus::Verified => "verified".to_string(),
             VerificationStatus::Failed => "failed".to_string(),
+            VerificationStatus::Cancelled => "cancelled".to_string(),
         }
     }
 }
 
-#[derive(Debug, AsExpression, FromSqlRow, Serialize, Clone)]
-#[diesel(sql_type = schema::sql_types::Network)]
-pub enum Network {
-    VaraMainnet,
-    VaraTestnet,
+/// Category of the verification failure
+#[derive(Debug, AsExpression, FromSqlRow, Serialize, ToSchema, Clone, Copy, PartialEq)]
+#[diesel(sql_type = schema::sql_types::Failurekind)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// Repository couldn't be cloned or the git ref wasn't found
+    CloneFailed,
+    /// Manifest of the project wasn't found at the requested path
+    ManifestNotFound,
+    /// Dependencies couldn't be fetched, the project failed to compile or the build ran out of memory
+    BuildFailed,
+    /// IDL of the project failed to build
+    IdlFailed,
+    /// Build didn't produce the optimized wasm
+    WasmMissing,
+    /// Built code differs from the code on chain
+    CodeMismatch,
+    /// Code or program doesn't exist on chain, or the program has exited or is terminated
+    CodeNotOnchain,
+    /// Build exceeded the time limit
+    Timeout,
+    /// Failure of the verifier itself, e.g. the network was unavailable
+    Internal,
 }
 
-impl ToSql<schema::sql_types::Network, diesel::pg::Pg> for Network {
+impl ToSql<schema::sql_types::Failurekind, diesel::pg::Pg> for FailureKind {
     fn to_sql<'b>(
         &'b self,
         out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
     ) -> diesel::serialize::Result {
         match &self {
-            Network::VaraMainnet => out.write_all(b"vara_mainnet")?,
-            Network::VaraTestnet => out.write_all(b"vara_testnet")?,
+            FailureKind::CloneFailed => out.write_all(b"clone_failed")?,
+            FailureKind::ManifestNotFound => out.write_all(b"manifest_not_found")?,
+            FailureKind::BuildFailed => out.write_all(b"build_failed")?,
+            FailureKind::IdlFailed => out.write_all(b"idl_failed")?,
+            FailureKind::WasmMissing => out.write_all(b"wasm_missing")?,
+            FailureKind::CodeMismatch => out.write_all(b"code_mismatch")?,
+            FailureKind::CodeNotOnchain => out.write_all(b"code_not_onchain")?,
+            FailureKind::Timeout => out.write_all(b"timeout")?,
+            FailureKind::Internal => out.write_all(b"internal")?,
         }
         Ok(IsNull::No)
     }
 }
 
-impl FromSql<schema::sql_types::Network, diesel::pg::Pg> for Network {
+impl FromSql<schema::sql_types::Failurekind, diesel::pg::Pg> for FailureKind {
     fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
         match bytes.as_bytes() {
-            b"vara_mainnet" => Ok(Network::VaraMainnet),
-            b"vara_testnet" => Ok(Network::VaraTestnet),
+            b"clone_failed" => Ok(FailureKind::CloneFailed),
+            b"manifest_not_found" => Ok(FailureKind::ManifestNotFound),
+            b"build_failed" => Ok(FailureKind::BuildFailed),
+            b"idl_failed" => Ok(FailureKind::IdlFailed),
+            b"wasm_missing" => Ok(FailureKind::WasmMissing),
+            b"code_mismatch" => Ok(FailureKind::CodeMismatch),
+            b"code_not_onchain" => Ok(FailureKind::CodeNotOnchain),
+            b"timeout" => Ok(FailureKind::Timeout),
+            b"internal" => Ok(FailureKind::Internal),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
 }
 
-impl From<Network> for String {
-    fn from(status: Network) -> String {
-        match status {
-            Network::VaraMainnet => "vara_mainnet".to_string(),
-            Network::VaraTestnet => "vara_testnet".to_string(),
+#[derive(Debug, AsExpression, FromSqlRow, Serialize, ToSchema, Clone, PartialEq)]
+#[diesel(sql_type = schema::sql_types::Imagestatus)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageStatus {
+    /// Verifier image is being built
+    Building,
+    /// Verifier image is built and verifications can be processed
+    Ready,
+    /// Verifier image failed to build, the version is unavailable
+    Failed,
+}
+
+impl ToSql<schema::sql_types::Imagestatus, diesel::pg::Pg> for ImageStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+    ) -> diesel::serialize::Result {
+        match &self {
+            ImageStatus::Building => out.write_all(b"building")?,
+            ImageStatus::Ready => out.write_all(b"ready")?,
+            ImageStatus::Failed => out.write_all(b"failed")?,
         }
+        Ok(IsNull::No)
     }
 }
 
-impl TryFrom<String> for Network {
-    type Error = anyhow::Error;
-
-    fn try_from(status: String) -> Result<Network, Self::Error> {
-        match status.as_str() {
-            "vara_mainnet" => Ok(Network::VaraMainnet),
-            "vara_testnet" => Ok(Network::VaraTestnet),
-            _ => anyhow::bail!(
-                "Unrecognized network name. Available options: vara_mainnet, vara_testnet"
-            ),
+impl FromSql<schema::sql_types::Imagestatus, diesel::pg::Pg> for ImageStatus {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"building" => Ok(ImageStatus::Building),
+            b"ready" => Ok(ImageStatus::Ready),
+            b"failed" => Ok(ImageStatus::Failed),
+            _ => Err("Unrecognized enum variant".into()),
         }
     }
 }
@@ -2,20 +2,45 @@
 
 pub mod sql_types {
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
-    #[diesel(postgres_type(name = "network"))]
-    pub struct Network;
+    #[diesel(postgres_type(name = "failurekind"))]
+    pub struct Failurekind;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "imagestatus"))]
+    pub struct Imagestatus;
 
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "verificationstatus"))]
     pub struct Verificationstatus;
 }
 
+diesel::table! {
+    api_key (id) {
+        id -> Varchar,
+        name -> Varchar,
+        key_hash -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     code (id) {
         id -> Varchar,
         idl_hash -> Nullable<Varchar>,
         name -> Varchar,
         repo_link -> Varchar,
+        commit_sha -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    code_programs (code_id, network) {
+        code_id -> Varchar,
+        network -> Varchar,
+        program_ids -> Array<Text>,
+        updated_at -> Timestamp,
     }
 }
 
@@ -26,25 +51,88 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    program (id, network) {
+        id -> Varchar,
+        code_id -> Varchar,
+        network -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Imagestatus;
+
+    sails_version (version) {
+        version -> Varchar,
+        created_at -> Timestamp,
+        status -> Imagestatus,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::Verificationstatus;
-    use super::sql_types::Network;
+    use super::sql_types::Failurekind;
 
     verification (id) {
         id -> Varchar,
         repo_link -> Varchar,
-        code_id -> Varchar,
+        code_id -> Nullable<Varchar>,
         project_name -> Nullable<Varchar>,
         manifest_path -> Nullable<Varchar>,
         build_idl -> Bool,
         version -> Varchar,
         status -> Verificationstatus,
-        network -> Network,
+        network -> Varchar,
         failed_reason -> Nullable<Text>,
         created_at -> Timestamp,
         base_path -> Nullable<Varchar>,
+        git_ref -> Nullable<Varchar>,
+        commit_sha -> Nullable<Varchar>,
+        built_code_len -> Nullable<Int4>,
+        built_code_hash -> Nullable<Varchar>,
+        onchain_code_len -> Nullable<Int4>,
+        onchain_code_hash -> Nullable<Varchar>,
+        diff_report -> Nullable<Jsonb>,
+        calculated_code_id -> Nullable<Varchar>,
+        wasm_name -> Nullable<Varchar>,
+        artifact_sizes -> Nullable<Jsonb>,
+        program_id -> Nullable<Varchar>,
+        callback_url -> Nullable<Varchar>,
+        idempotency_key -> Nullable<Varchar>,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamp>,
+        failure_kind -> Nullable<Failurekind>,
+        idempotency_scope -> Nullable<Varchar>,
+        request_hash -> Nullable<Varchar>,
+        submitter -> Nullable<Varchar>,
+        cancel_token_hash -> Nullable<Varchar>,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(code, idl, verification,);
+diesel::table! {
+    webhook_delivery (verification_id) {
+        verification_id -> Varchar,
+        url -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamp>,
+        delivered_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(webhook_delivery -> verification (verification_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_key,
+    code,
+    code_programs,
+    idl,
+    program,
+    sails_version,
+    verification,
+    webhook_delivery,
+);
@@ -1,10 +1,12 @@
 pub mod common;
+pub mod config;
 pub mod consts;
 pub mod db;
 mod processor;
 mod server;
 pub mod util;
 
-pub use processor::{network_client::Client, prune_containers, run_processor, *};
+pub use processor::{prune_containers, run_processor, *};
+pub use server::rate_limit::TokenBuckets;
 pub use server::run_server;
 pub use server::ApiDoc;
@@ -1,10 +1,11 @@
 use dotenvy::dotenv;
 use sails_program_verifier::{
-    build_verifier_image,
-    consts::AVAILABLE_VERSIONS,
+    config::Config,
     db::{get_connection_pool, Verification},
-    prune_containers, remove_dangling_images, run_processor, run_server,
-    util::{clean_or_create_logs_dir, create_verifier_dockerfile},
+    prepare_version_images, prune_containers, prune_volumes, run_files_pruning, run_processor,
+    run_server, run_webhooks,
+    util::{create_artifacts_dir, create_logs_dir},
+    AppClients,
 };
 use std::sync::Arc;
 
@@ -13,23 +14,36 @@ async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
+    log::info!("Loading config");
+    let config = Arc::new(Config::load()?);
+
     log::info!("Prunning old containers");
     prune_containers().await?;
 
-    for v in AVAILABLE_VERSIONS {
-        log::info!("Creating verifier dockerfile and image for version {v}");
-        create_verifier_dockerfile(v)?;
-        build_verifier_image(v).await?;
-    }
+    log::info!("Prunning old build volumes");
+    prune_volumes().await?;
 
-    log::info!("Removing dangling images");
-    remove_dangling_images().await?;
+    log::info!("Connecting to the database");
+    let pool = Arc::new(get_connection_pool(&config.database));
 
-    log::info!("Cleaning logs directory");
-    clean_or_create_logs_dir()?;
+    let images_pool = Arc::clone(&pool);
+    let images_config = Arc::clone(&config);
 
-    log::info!("Connecting to the database");
-    let pool = Arc::new(get_connection_pool());
+    // Verifications of a version stay pending until its image is ready
+    tokio::spawn(async move {
+        if let Err(error) = prepare_version_images(images_pool, images_config).await {
+            log::error!("Failed to prepare verifier images. {error:?}");
+        }
+    });
+
+    log::info!("Creating logs directory");
+    create_logs_dir(&config.builder.logs_dir)?;
+
+    log::info!("Creating artifacts directory");
+    create_artifacts_dir(&config.builder.artifacts_dir)?;
+
+    // The first check runs immediately, removing files expired while the service was stopped
+    tokio::spawn(run_files_pruning(Arc::clone(&config)));
 
     let server_pool = Arc::clone(&pool);
     let proc_pool = Arc::clone(&pool);
@@ -37,7 +51,19 @@ async fn main() -> anyhow::Result<()> {
     log::info!("Resetting in progress verifications");
     Verification::reset_in_progress(&mut server_pool.get().unwrap())?;
 
-    let proc_handle = run_processor(proc_pool).await?;
+    log::info!("Connecting to networks");
+    let clients = Arc::new(AppClients::connect(&config.networks).await?);
+
+    let proc_handle = run_processor(proc_pool, Arc::clone(&config), Arc::clone(&clients)).await?;
+
+    let webhooks_pool = Arc::clone(&pool);
+    let webhooks_config = Arc::clone(&config);
+
+    tokio::spawn(async move {
+        if let Err(error) = run_webhooks(webhooks_pool, webhooks_config).await {
+            log::error!("Failed to run callback delivery. {error:?}");
+        }
+    });
 
     tokio::spawn(async move {
         proc_handle.await.unwrap();
@@ -45,7 +71,7 @@ async fn main() -> anyhow::Result<()> {
     });
 
     tokio::spawn(async move {
-        run_server(server_pool).await;
+        run_server(server_pool, config, clients).await;
         log::info!("Server started successfully");
     })
     .await?;
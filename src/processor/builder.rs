@@ -1,42 +1,142 @@
-use super::docker::{build_program, remove_container};
+use super::docker::{
+    build_program, fetch_container_name, remove_build_volumes, remove_container, BuildError,
+};
 use crate::{
-    consts::{LOGS_DIR, PATH_TO_BUILDS},
+    common::Pool,
+    config::{BuilderConfig, Config},
     db::Verification,
-    util::generate_code_id,
+    util::{generate_code_id, get_artifact_path},
 };
 use anyhow::{bail, Result};
+use async_trait::async_trait;
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 pub struct BuildArtifacts {
+    pub code: Vec<u8>,
     pub code_id: String,
     pub idl: Option<String>,
     pub name: String,
+    pub wasm_name: String,
+    /// Sizes of the produced files in bytes, keyed by file name
+    pub sizes: BTreeMap<String, u64>,
+}
+
+/// Outcome of a project build
+pub struct Build {
+    pub artifacts: Result<BuildArtifacts>,
+    /// SHA of the checked out commit, available even if the build failed
+    pub commit_sha: Option<String>,
 }
 
-fn get_project_path(id: &str) -> PathBuf {
-    Path::new(PATH_TO_BUILDS).join(id)
+/// Builds projects of verifications
+#[async_trait]
+pub trait ProjectBuilder: Send + Sync {
+    /// Builds the project and removes its build files
+    async fn build(&self, verif: &Verification) -> Build;
+
+    /// Keeps the built code to let users inspect the mismatch
+    fn keep_artifact(&self, verif_id: &str, code: &[u8]) -> Result<()>;
+}
+
+/// Builds projects in Docker containers
+pub struct DockerBuilder {
+    pool: Arc<Pool>,
+    config: Arc<Config>,
+}
+
+impl DockerBuilder {
+    pub fn new(pool: Arc<Pool>, config: Arc<Config>) -> Self {
+        Self { pool, config }
+    }
 }
 
-pub async fn build_project(verif: Verification) -> Result<BuildArtifacts> {
-    let proj_path = get_project_path(&verif.id);
+#[async_trait]
+impl ProjectBuilder for DockerBuilder {
+    async fn build(&self, verif: &Verification) -> Build {
+        let config = &self.config.builder;
+
+        log::info!("{}: building project", &verif.id);
+        let artifacts = build_project(&self.pool, verif.clone(), config).await;
+        let commit_sha = read_commit_sha(config, &verif.id);
+
+        // The verification can still finish, leftover containers and volumes are pruned on restart
+        if let Err(err) = cleanup(config, &verif.id).await {
+            log::error!("{}: Failed to clean up the build. {err:?}", &verif.id);
+        }
+
+        Build {
+            artifacts,
+            commit_sha,
+        }
+    }
+
+    fn keep_artifact(&self, verif_id: &str, code: &[u8]) -> Result<()> {
+        fs::write(
+            get_artifact_path(&self.config.builder.artifacts_dir, verif_id),
+            code,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Name of the file where `build.sh` stores the SHA of the checked out commit
+const COMMIT_SHA_FILE: &str = "commit_sha";
+
+fn get_project_path(builds_dir: &Path, id: &str) -> PathBuf {
+    builds_dir.join(id)
+}
+
+/// Reads the commit SHA recorded by the builder container. It's available even if the build failed
+pub fn read_commit_sha(config: &BuilderConfig, verif_id: &str) -> Option<String> {
+    let commit_sha =
+        fs::read_to_string(get_project_path(&config.builds_dir, verif_id).join(COMMIT_SHA_FILE))
+            .ok()?;
+    let commit_sha = commit_sha.trim();
+
+    if commit_sha.len() == 40 && commit_sha.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(commit_sha.to_string())
+    } else {
+        None
+    }
+}
+
+pub async fn build_project(
+    pool: &Arc<Pool>,
+    verif: Verification,
+    config: &BuilderConfig,
+) -> Result<BuildArtifacts> {
+    let proj_path = get_project_path(&config.builds_dir, &verif.id);
 
     fs::create_dir_all(&proj_path)?;
     log::info!("{}: project dir created ({:?})", &verif.id, &proj_path);
 
-    build_program(&verif, proj_path.to_str().unwrap()).await?;
+    build_program(pool, &verif, proj_path.to_str().unwrap(), config).await?;
     log::info!("{}: program built", &verif.id);
 
     let built_files = fs::read_dir(&proj_path)?;
 
     let mut wasm_path: Option<PathBuf> = None;
     let mut idl_path: Option<PathBuf> = None;
+    let mut sizes = BTreeMap::new();
 
     for entry in built_files {
         let path = entry.as_ref().unwrap().path().to_str().unwrap().to_string();
         log::debug!("{:?} file found", &path);
+
+        let file_name = entry.as_ref().unwrap().file_name();
+        if file_name != COMMIT_SHA_FILE {
+            sizes.insert(
+                file_name.to_string_lossy().into_owned(),
+                entry.as_ref().unwrap().metadata()?.len(),
+            );
+        }
+
         if path.ends_with(".opt.wasm") {
             wasm_path = Some(entry.as_ref().unwrap().path());
         } else if path.ends_with(".idl") {
@@ -45,7 +145,7 @@ pub async fn build_project(verif: Verification) -> Result<BuildArtifacts> {
     }
 
     let Some(wasm_path) = wasm_path else {
-        bail!("Failed to build wasm.");
+        bail!(BuildError::WasmMissing);
     };
 
     log::info!("{}: wasm - {:?}", &verif.id, &wasm_path);
@@ -56,7 +156,7 @@ pub async fn build_project(verif: Verification) -> Result<BuildArtifacts> {
 
     let idl = if verif.build_idl {
         let Some(idl_path) = idl_path else {
-            bail!("Failed to build idl file.");
+            bail!(BuildError::IdlFailed);
         };
         log::info!("{}: idl - {:?}", &verif.id, &idl_path);
         fs::read_to_string(&idl_path).ok()
@@ -65,24 +165,22 @@ pub async fn build_project(verif: Verification) -> Result<BuildArtifacts> {
     };
 
     Ok(BuildArtifacts {
+        code,
         code_id,
         idl,
         name: code_filename[..(code_filename.len() - ".opt.wasm".len())].to_string(),
+        wasm_name: code_filename.to_string(),
+        sizes,
     })
 }
 
-pub async fn cleanup(verif_id: &str, success: bool) -> Result<()> {
-    fs::remove_dir_all(get_project_path(verif_id))?;
+pub async fn cleanup(config: &BuilderConfig, verif_id: &str) -> Result<()> {
+    fs::remove_dir_all(get_project_path(&config.builds_dir, verif_id))?;
     log::info!("{verif_id}: project dir cleaned");
 
+    remove_container(&fetch_container_name(verif_id)).await?;
     remove_container(verif_id).await?;
-
-    if success {
-        let log_file_path = format!("{LOGS_DIR}/{verif_id}.log");
-        if let Err(error) = std::fs::remove_file(&log_file_path) {
-            log::error!("Failed to remove logs file {}. {:?}", &log_file_path, error);
-        }
-    }
+    remove_build_volumes(verif_id).await?;
 
     Ok(())
 }
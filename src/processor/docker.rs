@@ -1,22 +1,154 @@
 use crate::{
-    consts::{IMAGE_NAME, LOGS_DIR},
-    db::Verification,
+    common::Pool,
+    config::BuilderConfig,
+    db::{FailureKind, Verification},
+    util::get_log_file_path,
 };
 use anyhow::{bail, Result};
 use bollard::{
     body_full,
-    models::ContainerCreateBody,
+    errors::Error as DockerError,
+    models::{ContainerCreateBody, VolumeCreateOptions},
     query_parameters::{
-        BuildImageOptionsBuilder, CreateContainerOptionsBuilder, ListContainersOptionsBuilder,
-        LogsOptionsBuilder, PruneImagesOptionsBuilder, RemoveContainerOptions,
-        RemoveContainerOptionsBuilder, StartContainerOptions, WaitContainerOptionsBuilder,
+        BuildImageOptionsBuilder, CreateContainerOptionsBuilder, InspectContainerOptions,
+        KillContainerOptions, ListContainersOptionsBuilder, ListVolumesOptionsBuilder,
+        LogsOptionsBuilder, PruneImagesOptionsBuilder, RemoveContainerOptionsBuilder,
+        StartContainerOptions, WaitContainerOptionsBuilder,
     },
     secret::{HostConfig, Mount, MountTypeEnum},
     Docker,
 };
-use futures::{StreamExt, TryStreamExt};
-use std::{collections::HashMap, io::Write};
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::{collections::HashMap, fmt, io::Write, sync::Arc, time::Duration};
 use tar::Builder;
+use tokio::time::Instant;
+
+/// Label of the volumes created for builds, used to find leftovers
+const VOLUME_LABEL: &str = "sails-program-verifier.verification";
+
+/// Exit code of `build.sh` when the requested git ref can't be resolved
+const EXIT_CODE_GIT_REF_NOT_FOUND: i64 = 10;
+
+/// Exit code of `build.sh` when the repository can't be cloned
+const EXIT_CODE_CLONE_FAILED: i64 = 11;
+
+/// Exit code of `build.sh` when the manifest or the base path of the project doesn't exist
+const EXIT_CODE_MANIFEST_NOT_FOUND: i64 = 12;
+
+/// Exit code of `build.sh` when the project fails to compile
+const EXIT_CODE_BUILD_FAILED: i64 = 13;
+
+/// Exit code of `build.sh` when the IDL fails to build
+const EXIT_CODE_IDL_FAILED: i64 = 14;
+
+/// Exit code of `build.sh` when dependencies of the project can't be fetched, e.g. `Cargo.lock` is outdated
+const EXIT_CODE_DEPENDENCIES_FAILED: i64 = 15;
+
+/// Build failures caused by the verified project rather than by the verifier itself
+#[derive(Debug)]
+pub enum BuildError {
+    GitRefNotFound(String),
+    CloneFailed,
+    DependenciesFailed,
+    ManifestNotFound,
+    CompileFailed,
+    IdlFailed,
+    WasmMissing,
+    OutOfMemory { memory: i64 },
+    Timeout { timeout: Duration },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::GitRefNotFound(git_ref) => {
+                write!(f, "Git ref {git_ref} not found in the repository")
+            }
+            BuildError::CloneFailed => write!(f, "Failed to clone the repository"),
+            BuildError::DependenciesFailed => {
+                write!(
+                    f,
+                    "Failed to fetch dependencies of the project, check that Cargo.lock is up to date"
+                )
+            }
+            BuildError::ManifestNotFound => {
+                write!(
+                    f,
+                    "Manifest of the project not found, check the project and the base path"
+                )
+            }
+            BuildError::CompileFailed => {
+                write!(f, "Failed to build the project, see the build logs")
+            }
+            BuildError::IdlFailed => write!(f, "Failed to build the IDL, see the build logs"),
+            BuildError::WasmMissing => write!(f, "Build didn't produce a .opt.wasm file"),
+            BuildError::OutOfMemory { memory } => write!(
+                f,
+                "Build was killed because it exceeded the memory limit of {} MiB",
+                memory / 1024 / 1024
+            ),
+            BuildError::Timeout { timeout } => {
+                write!(
+                    f,
+                    "Build timed out after {} minutes",
+                    timeout.as_secs() / 60
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl BuildError {
+    /// Whether the build may succeed if retried, e.g. the repository host was unavailable.
+    /// Dependency failures aren't retried, as they are mostly caused by the project itself
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BuildError::CloneFailed)
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            BuildError::GitRefNotFound(_) | BuildError::CloneFailed => FailureKind::CloneFailed,
+            BuildError::DependenciesFailed => FailureKind::BuildFailed,
+            BuildError::ManifestNotFound => FailureKind::ManifestNotFound,
+            BuildError::CompileFailed | BuildError::OutOfMemory { .. } => FailureKind::BuildFailed,
+            BuildError::IdlFailed => FailureKind::IdlFailed,
+            BuildError::WasmMissing => FailureKind::WasmMissing,
+            BuildError::Timeout { .. } => FailureKind::Timeout,
+        }
+    }
+}
+
+/// Host config of builder containers with the configured resource limits applied
+fn host_config(config: &BuilderConfig, mounts: Vec<Mount>) -> HostConfig {
+    HostConfig {
+        mounts: Some(mounts),
+        memory: Some(config.memory),
+        // Disables swap, so the memory limit is enforced
+        memory_swap: Some(config.memory),
+        nano_cpus: Some(config.nano_cpus),
+        pids_limit: Some(config.pids_limit),
+        storage_opt: config
+            .storage_size
+            .as_ref()
+            .map(|size| HashMap::from([("size".to_string(), size.clone())])),
+        ..Default::default()
+    }
+}
+
+async fn is_oom_killed(docker: &Docker, id: &str) -> bool {
+    match docker
+        .inspect_container(id, None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(info) => info.state.and_then(|s| s.oom_killed).unwrap_or_default(),
+        Err(e) => {
+            log::error!("{id}: Failed to inspect container: {e:?}");
+            false
+        }
+    }
+}
 
 pub async fn prune_containers() -> Result<()> {
     let docker = Docker::connect_with_local_defaults().unwrap();
@@ -41,24 +173,151 @@ pub async fn prune_containers() -> Result<()> {
 pub async fn remove_container(id: &str) -> Result<()> {
     let docker = Docker::connect_with_local_defaults()?;
 
+    match docker
+        .remove_container(
+            id,
+            Some(RemoveContainerOptionsBuilder::new().force(true).build()),
+        )
+        .await
+    {
+        Ok(()) => log::info!("{id}: container removed"),
+        // The container wasn't created, e.g. the build failed at the earlier stage
+        Err(DockerError::DockerResponseServerError {
+            status_code: 404, ..
+        }) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(())
+}
+
+/// Name of the container that fetches sources and dependencies of the verification
+pub fn fetch_container_name(verif_id: &str) -> String {
+    format!("{verif_id}-fetch")
+}
+
+/// Stops the build of the verification by removing its containers. The processor then fails
+/// to wait for the container, cleans up the build and releases its slot
+pub async fn stop_build(verif_id: &str) -> Result<()> {
+    remove_container(&fetch_container_name(verif_id)).await?;
+    remove_container(verif_id).await
+}
+
+/// Stops the build of the verification cancelled before its container was created,
+/// as [`stop_build`] can only remove the existing ones. Checked once the container exists,
+/// so later cancellations find it
+async fn check_cancelled(pool: &Arc<Pool>, verif_id: &str) -> Result<()> {
+    let pool = pool.clone();
+    let id = verif_id.to_string();
+
+    let cancelled = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().expect("Failed to get connection");
+        Verification::is_cancelled(&mut conn, &id)
+    })
+    .await?;
+
+    if cancelled {
+        bail!("Verification cancelled");
+    }
+
+    Ok(())
+}
+
+/// Volumes shared between the fetch and the compile containers and their mount points.
+/// Cargo caches are kept at their default location, as their paths end up in the built wasm.
+/// Rustup home is shared as well, so that toolchains pinned by `rust-toolchain.toml` and
+/// installed while fetching are available offline. Empty volumes are populated with
+/// the image content, so the toolchains of the image are kept.
+fn build_volumes(verif_id: &str) -> [(String, &'static str); 4] {
+    [
+        (format!("{verif_id}-src"), "/project"),
+        (format!("{verif_id}-registry"), "/usr/local/cargo/registry"),
+        (format!("{verif_id}-git"), "/usr/local/cargo/git"),
+        (format!("{verif_id}-rustup"), "/usr/local/rustup"),
+    ]
+}
+
+async fn create_build_volumes(docker: &Docker, verif_id: &str) -> Result<Vec<Mount>> {
+    let mut mounts = Vec::new();
+
+    for (name, target) in build_volumes(verif_id) {
+        docker
+            .create_volume(VolumeCreateOptions {
+                name: Some(name.clone()),
+                labels: Some(HashMap::from([(
+                    VOLUME_LABEL.to_string(),
+                    verif_id.to_string(),
+                )])),
+                ..Default::default()
+            })
+            .await?;
+
+        mounts.push(Mount {
+            source: Some(name),
+            target: Some(target.to_string()),
+            read_only: Some(false),
+            typ: Some(MountTypeEnum::VOLUME),
+            ..Default::default()
+        });
+    }
+
+    Ok(mounts)
+}
+
+// `remove_volume` of this bollard version accepts only the deprecated options type
+#[allow(deprecated)]
+async fn remove_volume(docker: &Docker, name: &str) -> Result<(), DockerError> {
     docker
-        .remove_container(id, None::<RemoveContainerOptions>)
+        .remove_volume(name, None::<bollard::volume::RemoveVolumeOptions>)
+        .await
+}
+
+pub async fn remove_build_volumes(verif_id: &str) -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+
+    for (name, _) in build_volumes(verif_id) {
+        match remove_volume(&docker, &name).await {
+            Ok(())
+            | Err(DockerError::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    log::info!("{verif_id}: volumes removed");
+
+    Ok(())
+}
+
+pub async fn prune_volumes() -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+
+    let filters = HashMap::from([("label", vec![VOLUME_LABEL])]);
+
+    let volumes = docker
+        .list_volumes(Some(
+            ListVolumesOptionsBuilder::new().filters(&filters).build(),
+        ))
         .await?;
 
-    log::info!("{id}: container removed");
+    for v in volumes.volumes.unwrap_or_default() {
+        remove_volume(&docker, &v.name).await?;
+    }
 
     Ok(())
 }
 
-async fn write_container_logs(docker: &Docker, id: &str) -> Result<()> {
+async fn write_container_logs(
+    docker: &Docker,
+    id: &str,
+    log_file: &mut std::fs::File,
+) -> Result<()> {
     let mut logs = docker.logs(
         id,
         Some(LogsOptionsBuilder::new().stdout(true).stderr(true).build()),
     );
 
-    let log_file_path = format!("{LOGS_DIR}/{id}.log");
-    let mut log_file = std::fs::File::create(&log_file_path)?;
-
     while let Some(log_chunk) = logs.next().await {
         match log_chunk {
             Ok(chunk) => {
@@ -74,11 +333,155 @@ async fn write_container_logs(docker: &Docker, id: &str) -> Result<()> {
     Ok(())
 }
 
-pub async fn build_program(verif: &Verification, project_path: &str) -> Result<String> {
+/// Attaches to the output of the container and returns its chunks as they appear.
+/// The stream ends when the container stops.
+pub async fn follow_container_logs(id: &str) -> Result<impl Stream<Item = Result<Vec<u8>>>> {
+    let docker = Docker::connect_with_local_defaults()?;
+
+    docker
+        .inspect_container(id, None::<InspectContainerOptions>)
+        .await?;
+
+    let logs = docker.logs(
+        id,
+        Some(
+            LogsOptionsBuilder::new()
+                .follow(true)
+                .stdout(true)
+                .stderr(true)
+                .build(),
+        ),
+    );
+
+    Ok(logs
+        .map_ok(|chunk| chunk.into_bytes().to_vec())
+        .map_err(anyhow::Error::from))
+}
+
+/// Runs the container until it stops or the deadline expires, and appends its output to the log file
+#[allow(clippy::too_many_arguments)]
+async fn run_container(
+    pool: &Arc<Pool>,
+    docker: &Docker,
+    verif: &Verification,
+    name: &str,
+    config: ContainerCreateBody,
+    builder_config: &BuilderConfig,
+    deadline: Instant,
+    log_file: &mut std::fs::File,
+) -> Result<()> {
+    let cc_options = CreateContainerOptionsBuilder::new().name(name).build();
+
+    let id = docker.create_container(Some(cc_options), config).await?.id;
+
+    log::info!("{}: container created({})", name, &id[0..12]);
+
+    // The container is removed by the cleanup
+    check_cancelled(pool, &verif.id).await?;
+
+    docker
+        .start_container(&id, Some(StartContainerOptions::default()))
+        .await?;
+
+    log::info!("{}: container started({})", name, &id[0..12]);
+
+    let wait = docker
+        .wait_container(
+            &id,
+            Some(
+                WaitContainerOptionsBuilder::new()
+                    .condition("not-running")
+                    .build(),
+            ),
+        )
+        .try_collect::<Vec<_>>();
+
+    let Ok(c_result) = tokio::time::timeout_at(deadline, wait).await else {
+        log::warn!("{}: build timed out, killing container", name);
+
+        if let Err(e) = docker
+            .kill_container(&id, None::<KillContainerOptions>)
+            .await
+        {
+            log::error!("{}: Failed to kill container: {:?}", name, e);
+        }
+
+        // Logs can be collected only after the container has stopped
+        let _ = docker
+            .wait_container(
+                &id,
+                Some(
+                    WaitContainerOptionsBuilder::new()
+                        .condition("not-running")
+                        .build(),
+                ),
+            )
+            .try_collect::<Vec<_>>()
+            .await;
+
+        write_container_logs(docker, &id, log_file).await?;
+
+        bail!(BuildError::Timeout {
+            timeout: builder_config.build_timeout()
+        });
+    };
+
+    if let Err(e) = c_result {
+        log::error!("{}: Failed to wait for container: {:?}", name, e);
+        write_container_logs(docker, &id, log_file).await?;
+
+        if is_oom_killed(docker, &id).await {
+            bail!(BuildError::OutOfMemory {
+                memory: builder_config.memory
+            });
+        }
+
+        if let DockerError::DockerContainerWaitError { code, .. } = e {
+            match code {
+                EXIT_CODE_GIT_REF_NOT_FOUND => bail!(BuildError::GitRefNotFound(
+                    verif.git_ref.clone().unwrap_or_default()
+                )),
+                EXIT_CODE_CLONE_FAILED => bail!(BuildError::CloneFailed),
+                EXIT_CODE_DEPENDENCIES_FAILED => bail!(BuildError::DependenciesFailed),
+                EXIT_CODE_MANIFEST_NOT_FOUND => bail!(BuildError::ManifestNotFound),
+                EXIT_CODE_BUILD_FAILED => bail!(BuildError::CompileFailed),
+                EXIT_CODE_IDL_FAILED => bail!(BuildError::IdlFailed),
+                _ => {}
+            }
+        }
+
+        bail!("Failed to wait for container");
+    } else {
+        for r in c_result.unwrap() {
+            log::info!(
+                "{}: error: {:?} || status code: {}",
+                name,
+                r.error,
+                r.status_code
+            );
+        }
+
+        write_container_logs(docker, &id, log_file).await?;
+
+        log::info!("{}: container exited({})", name, &id[0..12]);
+
+        Ok(())
+    }
+}
+
+/// Builds the program in two phases. The fetch phase clones the repository and downloads
+/// dependencies into volumes shared with the compile phase, which runs without network access.
+pub async fn build_program(
+    pool: &Arc<Pool>,
+    verif: &Verification,
+    project_path: &str,
+    config: &BuilderConfig,
+) -> Result<()> {
     log::debug!("{}: Start building program. {}", &verif.id, project_path);
     let docker = Docker::connect_with_local_defaults()?;
 
-    let cc_options = CreateContainerOptionsBuilder::new().name(&verif.id).build();
+    let deadline = Instant::now() + config.build_timeout();
+    let mut log_file = std::fs::File::create(get_log_file_path(&config.logs_dir, &verif.id))?;
 
     let repo_url_env = format!("REPO_URL={}", &verif.repo_link);
     let project_name_env = format!(
@@ -90,90 +493,82 @@ pub async fn build_program(verif: &Verification, project_path: &str) -> Result<S
         &verif.manifest_path.clone().unwrap_or_default()
     );
     let base_path_env = format!("BASE_PATH={}", &verif.base_path.clone().unwrap_or_default());
+    let git_ref_env = format!("GIT_REF={}", &verif.git_ref.clone().unwrap_or_default());
 
     let mut env: Vec<String> = vec![
         repo_url_env,
         project_name_env,
         manifest_path_env,
         base_path_env,
+        git_ref_env,
     ];
 
     if verif.build_idl {
         env.push("BUILD_IDL=true".to_string());
     }
 
-    let image = format!("{}:{}", IMAGE_NAME, &verif.version);
+    let image = format!("{}:{}", config.image_name, &verif.version);
 
-    let mount = Mount {
+    let mut mounts = create_build_volumes(&docker, &verif.id).await?;
+    mounts.push(Mount {
         source: Some(project_path.to_string()),
         target: Some("/mnt/target".to_string()),
         read_only: Some(false),
         typ: Some(MountTypeEnum::BIND),
         ..Default::default()
+    });
+
+    let fetch_config = ContainerCreateBody {
+        image: Some(image.clone()),
+        env: Some([env.clone(), vec!["BUILD_PHASE=fetch".to_string()]].concat()),
+        host_config: Some(host_config(config, mounts.clone())),
+        attach_stderr: Some(true),
+        attach_stdout: Some(true),
+        ..Default::default()
     };
 
-    let cc_config = ContainerCreateBody {
+    run_container(
+        pool,
+        &docker,
+        verif,
+        &fetch_container_name(&verif.id),
+        fetch_config,
+        config,
+        deadline,
+        &mut log_file,
+    )
+    .await?;
+
+    log::info!("{}: sources fetched", &verif.id);
+
+    let compile_config = ContainerCreateBody {
         image: Some(image),
-        env: Some(env),
+        env: Some([env, vec!["BUILD_PHASE=compile".to_string()]].concat()),
         host_config: Some(HostConfig {
-            mounts: Some(vec![mount]),
-            ..Default::default()
+            network_mode: Some("none".to_string()),
+            ..host_config(config, mounts)
         }),
         attach_stderr: Some(true),
         attach_stdout: Some(true),
         ..Default::default()
     };
 
-    let id = docker
-        .create_container(Some(cc_options), cc_config)
-        .await?
-        .id;
-
-    log::info!("{}: container created({})", &verif.id, &id[0..12]);
-
-    docker
-        .start_container(&id, Some(StartContainerOptions::default()))
-        .await?;
-
-    log::info!("{}: container started({})", &verif.id, &id[0..12]);
-
-    let c_result = docker
-        .wait_container(
-            &id,
-            Some(
-                WaitContainerOptionsBuilder::new()
-                    .condition("not-running")
-                    .build(),
-            ),
-        )
-        .try_collect::<Vec<_>>()
-        .await;
-
-    if let Err(e) = c_result {
-        log::error!("{}: Failed to wait for container: {:?}", &verif.id, e);
-        write_container_logs(&docker, &verif.id).await?;
-        bail!("Failed to wait for container");
-    } else {
-        for r in c_result.unwrap() {
-            log::info!(
-                "{}: error: {:?} || status code: {}",
-                &verif.id,
-                r.error,
-                r.status_code
-            );
-        }
-
-        write_container_logs(&docker, &verif.id).await?;
-
-        log::info!("{}: container exited({})", &verif.id, &id[0..12]);
-
-        Ok(id)
-    }
+    run_container(
+        pool,
+        &docker,
+        verif,
+        &verif.id,
+        compile_config,
+        config,
+        deadline,
+        &mut log_file,
+    )
+    .await
 }
 
-pub async fn build_verifier_image(version: &str) -> Result<()> {
+pub async fn build_verifier_image(version: &str, config: &BuilderConfig) -> Result<()> {
     let docker = Docker::connect_with_local_defaults()?;
-    let image_name = format!("verifier:{version}");
+    let image_name = format!("{}:{version}", config.image_name);
 
     let options = BuildImageOptionsBuilder::new()
         .dockerfile("Dockerfile")
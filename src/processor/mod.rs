@@ -1,20 +1,15 @@
 use crate::{
     common::Pool,
-    db::{Code, Idl, Network, Verification, VerificationStatus},
-    util::hash_idl,
-    Client,
+    config::Config,
+    db::{FailureKind, ImageStatus, SailsVersion, Verification},
+    util::{create_verifier_dockerfile, prune_old_files},
 };
-use anyhow::{anyhow, bail, Result};
-use builder::{build_project, cleanup};
+use anyhow::{bail, Result};
+use diesel::Connection;
 use futures::{Stream, StreamExt};
-use network_client::AppClients;
-use std::{
-    env,
-    sync::{
-        atomic::{AtomicI64, Ordering},
-        Arc,
-    },
-    time::Duration,
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
 };
 use tokio::{task::JoinHandle, time};
 use tokio_stream::wrappers::IntervalStream;
@@ -22,16 +17,34 @@ use tokio_stream::wrappers::IntervalStream;
 mod builder;
 mod docker;
 pub mod network_client;
-pub use docker::{build_verifier_image, prune_containers, remove_dangling_images};
-
-const MAX_VERIFS_IN_PROGRESS: i64 = 10;
-const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+mod onchain;
+mod pipeline;
+mod store;
+mod wasm_diff;
+mod webhook;
+pub use builder::{Build, BuildArtifacts, DockerBuilder, ProjectBuilder};
+pub use docker::{
+    build_verifier_image, fetch_container_name, follow_container_logs, prune_containers,
+    prune_volumes, remove_dangling_images, stop_build,
+};
+pub use network_client::{
+    AppClients, ChainClient, Client, CodeInfo, InvalidId, MemoryClient, ProgramState,
+};
+pub use onchain::{
+    compare_code, ensure_code_onchain, fetch_original_code, resolve_code_id, ChainFailure,
+};
+pub use pipeline::process_verification;
+pub use store::{MemoryStore, VerificationStore};
+pub use wasm_diff::{diff_wasm, FunctionsDiff, SectionDiff, SetDiff, WasmDiffReport};
+pub use webhook::{run_webhooks, sign_payload, SIGNATURE_HEADER};
 
 fn new_verifications(
     pool: Arc<Pool>,
+    config: Arc<Config>,
     verifs_in_progress: Arc<AtomicI64>,
 ) -> impl Stream<Item = Verification> {
-    let mut check_interval = time::interval(CHECK_INTERVAL);
+    let max_verifs_in_progress = config.processor.max_verifs_in_progress;
+    let mut check_interval = time::interval(config.processor.check_interval());
     check_interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
 
     IntervalStream::new(check_interval)
@@ -41,7 +54,7 @@ fn new_verifications(
 
             async move {
                 let in_progress = verifs_in_progress.load(Ordering::Relaxed);
-                if in_progress >= MAX_VERIFS_IN_PROGRESS {
+                if in_progress >= max_verifs_in_progress {
                     return None;
                 }
 
@@ -49,7 +62,7 @@ fn new_verifications(
                     let mut conn = pool_clone
                         .get()
                         .expect("Failed to get connection from the pool");
-                    Verification::get_pending(&mut conn, MAX_VERIFS_IN_PROGRESS - in_progress)
+                    Verification::get_pending(&mut conn, max_verifs_in_progress - in_progress)
                 })
                 .await
                 .ok()
@@ -58,31 +71,135 @@ fn new_verifications(
         .flat_map(futures::stream::iter)
 }
 
-pub async fn run_processor(pool: Arc<Pool>) -> anyhow::Result<JoinHandle<()>> {
-    let mut clients = AppClients::default();
+/// Builds the verifier image of the version and records whether the version is ready to be used
+pub async fn prepare_version_image(pool: Arc<Pool>, config: Arc<Config>, version: String) {
+    log::info!("Creating verifier dockerfile and image for version {version}");
 
-    if let Ok(url) = env::var("MAINNET_URL") {
-        clients.set(Network::VaraMainnet, Client::new(&url).await?);
+    let result = match create_verifier_dockerfile(&version, &config.builder.base_image) {
+        Ok(()) => build_verifier_image(&version, &config.builder).await,
+        Err(error) => Err(error),
     };
-    if let Ok(url) = env::var("TESTNET_URL") {
-        clients.set(Network::VaraTestnet, Client::new(&url).await?);
+
+    let status = match result {
+        Ok(()) => {
+            log::info!("Verifier image for version {version} is ready");
+            ImageStatus::Ready
+        }
+        Err(error) => {
+            log::error!("Failed to build verifier image for version {version}. {error:?}");
+            ImageStatus::Failed
+        }
     };
 
+    let result = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().expect("Failed to get connection");
+        let is_failed = status == ImageStatus::Failed;
+
+        // Verifications saved concurrently either see the failed status or are failed here
+        conn.transaction(|conn| {
+            SailsVersion::update_status(conn, &version, status)?;
+
+            // Otherwise they would stay pending until the version is registered again
+            if is_failed {
+                let failed = Verification::fail_pending_of_version(
+                    conn,
+                    &version,
+                    FailureKind::Internal,
+                    &format!("Verifier image for Sails version {version} failed to build"),
+                )?;
+                log::warn!("Failed {failed} pending verifications of version {version}");
+            }
+
+            anyhow::Ok(())
+        })
+    })
+    .await;
+
+    if !matches!(result, Ok(Ok(_))) {
+        log::error!("Failed to update version status. {result:?}");
+    }
+}
+
+/// Builds verifier images of all supported versions concurrently
+pub async fn prepare_version_images(pool: Arc<Pool>, config: Arc<Config>) -> Result<()> {
+    let versions = {
+        let pool = pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get().expect("Failed to get connection");
+            SailsVersion::reset_status(&mut conn)?;
+            SailsVersion::get_all(&mut conn)
+        })
+        .await??
+    };
+
+    futures::future::join_all(
+        versions
+            .into_iter()
+            .map(|v| prepare_version_image(pool.clone(), config.clone(), v.version)),
+    )
+    .await;
+
+    log::info!("Removing dangling images");
+    remove_dangling_images().await?;
+
+    Ok(())
+}
+
+/// Interval between removals of expired build logs and artifacts
+const FILES_PRUNE_INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+
+/// Periodically removes build logs and kept artifacts older than the configured TTL
+pub async fn run_files_pruning(config: Arc<Config>) {
+    let mut interval = time::interval(FILES_PRUNE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let config = config.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let ttl = config.builder.files_ttl();
+            let logs = prune_old_files(&config.builder.logs_dir, ttl)?;
+            let artifacts = prune_old_files(&config.builder.artifacts_dir, ttl)?;
+            anyhow::Ok(logs + artifacts)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(0)) => {}
+            Ok(Ok(removed)) => log::info!("Removed {removed} expired build files"),
+            _ => log::error!("Failed to remove expired build files. {result:?}"),
+        }
+    }
+}
+
+/// Processes pending verifications, querying networks through the clients
+pub async fn run_processor(
+    pool: Arc<Pool>,
+    config: Arc<Config>,
+    clients: Arc<AppClients>,
+) -> anyhow::Result<JoinHandle<()>> {
     if clients.is_empty() {
-        panic!("No network clients are configured");
+        bail!("No network clients are configured");
     }
 
-    let clients = Arc::new(clients);
+    log::info!("Builder config: {:?}", config.builder);
 
     let handle = tokio::spawn(async move {
         let in_progress = Arc::new(AtomicI64::new(0));
-        let new_verifications = new_verifications(pool.clone(), in_progress.clone());
+        let new_verifications =
+            new_verifications(pool.clone(), config.clone(), in_progress.clone());
         futures::pin_mut!(new_verifications);
 
         loop {
             tokio::select! {
                 verif = new_verifications.next() => {
-                    process_verif(verif, pool.clone(), in_progress.clone(), clients.clone());
+                    process_verif(
+                        verif,
+                        pool.clone(),
+                        in_progress.clone(),
+                        clients.clone(),
+                        config.clone(),
+                    );
                 }
             }
         }
@@ -96,141 +213,16 @@ fn process_verif(
     pool: Arc<Pool>,
     in_progress: Arc<AtomicI64>,
     clients: Arc<AppClients>,
+    config: Arc<Config>,
 ) {
     if let Some(verif) = verif {
         in_progress.fetch_add(1, Ordering::Relaxed);
 
         tokio::spawn(async move {
-            let id = verif.id.clone();
-
-            let pool_clone = Arc::clone(&pool);
-            let verif_clone = verif.clone();
-
-            if let Err(err) = start_verification(pool_clone.clone(), verif_clone.clone()).await {
-                log::warn!("{}: {:?}", &id, err);
-            } else if let Err(err) =
-                check_code_onchain(clients.clone(), pool_clone.clone(), verif_clone.clone()).await
-            {
-                log::error!("{}: {:?}", &id, err);
-            } else if let Err(err) = build_and_verify(pool_clone.clone(), verif.clone()).await {
-                log::error!("{}: {:?}", &id, err);
-            }
+            let builder = DockerBuilder::new(pool.clone(), config.clone());
+            process_verification(pool.as_ref(), &builder, &clients, &config.processor, verif).await;
 
             in_progress.fetch_sub(1, Ordering::Relaxed);
         });
     }
 }
-
-async fn start_verification(pool: Arc<Pool>, verif: Verification) -> Result<()> {
-    tokio::task::spawn_blocking(move || {
-        let mut conn = pool.get().expect("Failed to get connection");
-        Verification::update(&mut conn, &verif.id, VerificationStatus::InProgress, None)?;
-
-        // Check if the code is already verified or the verification is in progress
-        if Code::get(&mut conn, &verif.code_id).is_some() {
-            Verification::update(&mut conn, &verif.id, VerificationStatus::Verified, None)?;
-            Err(anyhow!("Code already verified"))
-        } else if Verification::is_verification_in_progress(&mut conn, &verif.code_id, &verif.id) {
-            Verification::update(&mut conn, &verif.id, VerificationStatus::Pending, None)?;
-            Err(anyhow!("Verification in progress"))
-        } else {
-            Ok(())
-        }
-    })
-    .await?
-}
-
-async fn check_code_onchain(
-    clients: Arc<AppClients>,
-    pool: Arc<Pool>,
-    verif: Verification,
-) -> Result<()> {
-    let Ok(client) = clients.get(&verif.network) else {
-        tokio::task::spawn_blocking(move || {
-            let mut conn = pool.get().expect("Failed to get connection");
-            Verification::update(
-                &mut conn,
-                &verif.id,
-                VerificationStatus::Failed,
-                Some("Unsupported network".into()),
-            )
-        })
-        .await??;
-        bail!("Unsupported network");
-    };
-
-    if !client.check_code_onchain(verif.code_id.clone()).await? {
-        tokio::task::spawn_blocking(move || {
-            let mut conn = pool.get().expect("Failed to get connection");
-            Verification::update(
-                &mut conn,
-                &verif.id,
-                VerificationStatus::Failed,
-                Some("Code doesn't exist on chain".into()),
-            )
-        })
-        .await??;
-        bail!("Code doesn't exist on chain");
-    };
-
-    Ok(())
-}
-
-async fn build_and_verify(pool: Arc<Pool>, verif: Verification) -> Result<()> {
-    log::info!("{}: building project", &verif.id);
-    let build_res = build_project(verif.clone()).await;
-
-    cleanup(&verif.id, build_res.is_ok()).await?;
-
-    tokio::task::spawn_blocking(move || {
-        let mut conn = pool.get().expect("Failed to get connection");
-
-        if let Err(err) = build_res {
-            let err_msg = format!("Failed to build project. {err:?}");
-            Verification::update(
-                &mut conn,
-                &verif.id,
-                VerificationStatus::Failed,
-                Some(err_msg.clone()),
-            )?;
-            bail!(err_msg);
-        }
-
-        let artifacts = build_res.unwrap();
-
-        if artifacts.code_id != verif.code_id {
-            Verification::update(
-                &mut conn,
-                &verif.id,
-                VerificationStatus::Failed,
-                Some("Code ID mismatch".into()),
-            )?;
-            bail!(
-                "Code ID mismatch. Provided: {}. Calculated: {}",
-                &verif.code_id,
-                &artifacts.code_id,
-            );
-        } else {
-            let mut idl_hash: Option<String> = None;
-
-            if let Some(idl) = artifacts.idl {
-                idl_hash = Some(hash_idl(&idl));
-                Idl::save(&mut conn, idl_hash.as_ref().unwrap(), idl)?;
-                log::info!("{}: idl saved", &verif.id);
-            }
-            Code::new(
-                &mut conn,
-                verif.code_id,
-                verif.repo_link,
-                artifacts.name,
-                idl_hash,
-            )?;
-            log::info!("{}: code meta saved", &verif.id);
-            Verification::update(&mut conn, &verif.id, VerificationStatus::Verified, None)?;
-            log::info!("{}: verification completed", &verif.id);
-        }
-
-        Ok(())
-    })
-    .await?
-}
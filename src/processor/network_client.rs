@@ -1,11 +1,86 @@
-use crate::db::Network;
-use anyhow::{bail, Result};
+use crate::{config::NetworkConfig, util::generate_code_id};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
 use gsdk::{
-    metadata::{runtime_types::gear_common::CodeMetadata, storage::GearProgramStorage},
+    ext::codec::Decode,
+    metadata::{
+        runtime_types::{gear_common::CodeMetadata, gear_core::program::Program},
+        storage::GearProgramStorage,
+    },
     Api, Value,
 };
 use hex::FromHex;
+use std::{collections::HashMap, fmt, sync::Arc};
 
+/// Metadata of the code uploaded to the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeInfo {
+    /// Account that uploaded the code
+    pub author: String,
+    /// Block where the code was uploaded
+    pub block_number: u32,
+}
+
+/// State of a program in the network storage
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramState {
+    /// Program is running the code with the ID
+    Active(String),
+    /// Program has exited, its code can't be resolved anymore
+    Exited,
+    /// Program is terminated, its code can't be resolved anymore
+    Terminated,
+}
+
+/// Chain queries used by the processor
+#[async_trait]
+pub trait ChainClient: Send + Sync {
+    /// Hex encoded genesis hash of the network, without `0x` prefix
+    fn genesis_hash(&self) -> String;
+
+    async fn check_code_onchain(&self, code_id: &str) -> Result<bool> {
+        Ok(self.code_metadata(code_id).await?.is_some())
+    }
+
+    /// Original wasm of the code, as it was uploaded
+    async fn original_code(&self, code_id: &str) -> Result<Option<Vec<u8>>>;
+
+    async fn code_metadata(&self, code_id: &str) -> Result<Option<CodeInfo>>;
+
+    /// State of the program, `None` if the program doesn't exist
+    async fn program_state(&self, program_id: &str) -> Result<Option<ProgramState>>;
+
+    /// IDs of the active programs running the code
+    async fn code_programs(&self, code_id: &str) -> Result<Vec<String>>;
+}
+
+/// ID passed to a query isn't a hex encoded 32 byte value, so retrying the query won't help
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidId {
+    Code,
+    Program,
+}
+
+impl fmt::Display for InvalidId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidId::Code => write!(f, "Invalid code ID"),
+            InvalidId::Program => write!(f, "Invalid program ID"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidId {}
+
+fn parse_code_id(code_id: &str) -> Result<[u8; 32]> {
+    <[u8; 32]>::from_hex(code_id).map_err(|_| InvalidId::Code.into())
+}
+
+fn parse_program_id(program_id: &str) -> Result<[u8; 32]> {
+    <[u8; 32]>::from_hex(program_id).map_err(|_| InvalidId::Program.into())
+}
+
+/// Client of a Vara node
 pub struct Client {
     api: Api,
 }
@@ -17,58 +92,231 @@ impl Client {
         Ok(Self { api })
     }
 
-    pub async fn check_code_onchain(&self, code_id: String) -> Result<bool> {
-        let code_id = <[u8; 32]>::from_hex(code_id);
+    /// Connects to the first available node of the network, skipping nodes of other networks
+    pub async fn connect(network: &NetworkConfig) -> Result<Self> {
+        for url in &network.rpc_urls {
+            let client = match Self::new(url).await {
+                Ok(client) => client,
+                Err(error) => {
+                    log::warn!("{}: Failed to connect to {url}. {error:?}", network.name);
+                    continue;
+                }
+            };
 
-        let Ok(code_id) = code_id else {
-            bail!("Invalid code ID");
-        };
+            let genesis_hash = client.genesis_hash();
+            match &network.genesis_hash {
+                Some(expected)
+                    if !expected
+                        .trim_start_matches("0x")
+                        .eq_ignore_ascii_case(&genesis_hash) =>
+                {
+                    log::warn!(
+                        "{}: Node {url} has unexpected genesis hash 0x{genesis_hash}",
+                        network.name
+                    );
+                }
+                _ => return Ok(client),
+            }
+        }
 
-        let addr = Api::storage(
-            GearProgramStorage::MetadataStorage,
-            vec![Value::from_bytes(code_id)],
-        );
+        bail!("No available nodes of network {}", network.name)
+    }
 
-        let result: gsdk::Result<CodeMetadata> = self.api.fetch_storage(&addr).await;
+    /// Fetches the value of the program pallet storage, `None` if the key doesn't exist
+    async fn fetch_program_storage<T: Decode>(
+        &self,
+        storage: GearProgramStorage,
+        key: [u8; 32],
+    ) -> Result<Option<T>> {
+        let addr = Api::storage(storage, vec![Value::from_bytes(key)]);
 
-        Ok(result.is_ok())
+        match self.api.fetch_storage(&addr).await {
+            Ok(value) => Ok(Some(value)),
+            Err(gsdk::Error::StorageNotFound) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
     }
 }
 
+#[async_trait]
+impl ChainClient for Client {
+    fn genesis_hash(&self) -> String {
+        hex::encode(self.api.genesis_hash())
+    }
+
+    async fn original_code(&self, code_id: &str) -> Result<Option<Vec<u8>>> {
+        self.fetch_program_storage(
+            GearProgramStorage::OriginalCodeStorage,
+            parse_code_id(code_id)?,
+        )
+        .await
+    }
+
+    async fn code_metadata(&self, code_id: &str) -> Result<Option<CodeInfo>> {
+        let metadata: Option<CodeMetadata> = self
+            .fetch_program_storage(GearProgramStorage::MetadataStorage, parse_code_id(code_id)?)
+            .await?;
+
+        Ok(metadata.map(|m| CodeInfo {
+            author: hex::encode(m.author),
+            block_number: m.block_number,
+        }))
+    }
+
+    async fn program_state(&self, program_id: &str) -> Result<Option<ProgramState>> {
+        let program: Option<Program<u32>> = self
+            .fetch_program_storage(
+                GearProgramStorage::ProgramStorage,
+                parse_program_id(program_id)?,
+            )
+            .await?;
+
+        Ok(program.map(|program| match program {
+            Program::Active(program) => ProgramState::Active(hex::encode(program.code_hash)),
+            Program::Exited(_) => ProgramState::Exited,
+            Program::Terminated(_) => ProgramState::Terminated,
+        }))
+    }
+
+    async fn code_programs(&self, code_id: &str) -> Result<Vec<String>> {
+        let code_id = parse_code_id(code_id)?;
+
+        // The program storage can't be queried by code, so all programs are scanned
+        let mut programs = self
+            .api
+            .get_storage(None)
+            .await?
+            .iter(Api::storage_root(GearProgramStorage::ProgramStorage))
+            .await?;
+
+        let mut program_ids = Vec::new();
+
+        while let Some(entry) = programs.next().await {
+            let entry = entry?;
+
+            let Program::Active(program) = Program::<u32>::decode(&mut entry.value.encoded())?
+            else {
+                continue;
+            };
+
+            if program.code_hash.as_ref() == code_id {
+                // Program ID is stored unhashed at the end of the key
+                let key = &entry.key_bytes[entry.key_bytes.len() - 32..];
+                program_ids.push(hex::encode(key));
+            }
+        }
+
+        Ok(program_ids)
+    }
+}
+
+/// In-memory stand-in for a network, seeded with codes. Allows to run the processor offline
 #[derive(Default)]
-pub struct AppClients {
-    pub mainnet: Option<Client>,
-    pub testnet: Option<Client>,
+pub struct MemoryClient {
+    genesis_hash: String,
+    codes: HashMap<String, (Vec<u8>, CodeInfo)>,
+    programs: HashMap<String, ProgramState>,
 }
 
-impl AppClients {
-    pub fn set(&mut self, network: Network, client: Client) {
-        match network {
-            Network::VaraMainnet => self.mainnet = Some(client),
-            Network::VaraTestnet => self.testnet = Some(client),
+impl MemoryClient {
+    pub fn new(genesis_hash: &str) -> Self {
+        Self {
+            genesis_hash: genesis_hash.trim_start_matches("0x").to_string(),
+            codes: HashMap::new(),
+            programs: HashMap::new(),
         }
     }
 
-    pub fn get(&self, network: &Network) -> anyhow::Result<&Client> {
-        match network {
-            Network::VaraMainnet => {
-                if let Some(client) = &self.mainnet {
-                    Ok(client)
-                } else {
-                    Err(anyhow::anyhow!("Mainnet client is not configured"))
-                }
-            }
-            Network::VaraTestnet => {
-                if let Some(client) = &self.testnet {
-                    Ok(client)
-                } else {
-                    Err(anyhow::anyhow!("Testnet client is not configured"))
-                }
-            }
+    /// Adds an active program running the code
+    pub fn with_program(self, program_id: &str, code_id: &str) -> Self {
+        self.with_program_state(program_id, ProgramState::Active(code_id.to_string()))
+    }
+
+    /// Adds a program in the state, e.g. an exited one
+    pub fn with_program_state(mut self, program_id: &str, state: ProgramState) -> Self {
+        self.programs.insert(program_id.to_string(), state);
+        self
+    }
+
+    /// Adds the code as if it was uploaded by the author at the block
+    pub fn with_code(mut self, code: Vec<u8>, info: CodeInfo) -> Self {
+        self.codes.insert(generate_code_id(&code), (code, info));
+        self
+    }
+}
+
+#[async_trait]
+impl ChainClient for MemoryClient {
+    fn genesis_hash(&self) -> String {
+        self.genesis_hash.clone()
+    }
+
+    async fn original_code(&self, code_id: &str) -> Result<Option<Vec<u8>>> {
+        parse_code_id(code_id)?;
+
+        Ok(self.codes.get(code_id).map(|(code, _)| code.clone()))
+    }
+
+    async fn code_metadata(&self, code_id: &str) -> Result<Option<CodeInfo>> {
+        parse_code_id(code_id)?;
+
+        Ok(self.codes.get(code_id).map(|(_, info)| info.clone()))
+    }
+
+    async fn program_state(&self, program_id: &str) -> Result<Option<ProgramState>> {
+        parse_program_id(program_id)?;
+
+        Ok(self.programs.get(program_id).cloned())
+    }
+
+    async fn code_programs(&self, code_id: &str) -> Result<Vec<String>> {
+        parse_code_id(code_id)?;
+
+        let mut program_ids: Vec<String> = self
+            .programs
+            .iter()
+            .filter(|(_, state)| matches!(state, ProgramState::Active(id) if id == code_id))
+            .map(|(program_id, _)| program_id.clone())
+            .collect();
+        program_ids.sort();
+
+        Ok(program_ids)
+    }
+}
+
+/// Clients of the configured networks, keyed by network name
+#[derive(Default)]
+pub struct AppClients(HashMap<String, Arc<dyn ChainClient>>);
+
+impl AppClients {
+    /// Connects to all configured networks
+    pub async fn connect(networks: &[NetworkConfig]) -> Result<Self> {
+        let mut clients = Self::default();
+
+        for network in networks {
+            let client = Client::connect(network).await?;
+            log::info!(
+                "{}: connected, genesis hash 0x{}",
+                network.name,
+                client.genesis_hash()
+            );
+            clients.set(&network.name, Arc::new(client));
         }
+
+        Ok(clients)
+    }
+
+    pub fn set(&mut self, network: &str, client: Arc<dyn ChainClient>) {
+        self.0.insert(network.to_string(), client);
+    }
+
+    pub fn get(&self, network: &str) -> Result<&Arc<dyn ChainClient>> {
+        self.0
+            .get(network)
+            .ok_or_else(|| anyhow!("Network {network} is not configured"))
     }
 
     pub fn is_empty(&self) -> bool {
-        self.mainnet.is_none() && self.testnet.is_none()
+        self.0.is_empty()
     }
 }
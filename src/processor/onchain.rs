@@ -0,0 +1,113 @@
+use super::network_client::{ChainClient, InvalidId, ProgramState};
+use crate::db::FailureKind;
+
+/// Reason the verification can't proceed, found while checking it against the network
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainFailure {
+    pub kind: FailureKind,
+    /// Reason shown to users
+    pub reason: String,
+    /// Whether the check may succeed if retried, e.g. the node was unavailable
+    pub transient: bool,
+}
+
+impl ChainFailure {
+    fn permanent(kind: FailureKind, reason: &str) -> Self {
+        Self {
+            kind,
+            reason: reason.to_string(),
+            transient: false,
+        }
+    }
+
+    fn transient(reason: &str) -> Self {
+        Self {
+            kind: FailureKind::Internal,
+            reason: reason.to_string(),
+            transient: true,
+        }
+    }
+}
+
+/// Failure of a chain query. Invalid IDs are permanent failures,
+/// other errors, e.g. an unavailable node, are retried
+fn query_failure(err: anyhow::Error, reason: &str) -> ChainFailure {
+    match err.downcast_ref::<InvalidId>() {
+        Some(invalid) => ChainFailure::permanent(FailureKind::CodeNotOnchain, &invalid.to_string()),
+        None => ChainFailure::transient(reason),
+    }
+}
+
+/// Code ID of the requested program
+pub async fn resolve_code_id(
+    client: &dyn ChainClient,
+    program_id: &str,
+) -> Result<String, ChainFailure> {
+    match client.program_state(program_id).await {
+        Ok(Some(ProgramState::Active(code_id))) => Ok(code_id),
+        Ok(Some(ProgramState::Exited)) => Err(ChainFailure::permanent(
+            FailureKind::CodeNotOnchain,
+            "Program has exited",
+        )),
+        Ok(Some(ProgramState::Terminated)) => Err(ChainFailure::permanent(
+            FailureKind::CodeNotOnchain,
+            "Program is terminated",
+        )),
+        Ok(None) => Err(ChainFailure::permanent(
+            FailureKind::CodeNotOnchain,
+            "Program doesn't exist on chain",
+        )),
+        Err(err) => {
+            log::warn!("Failed to resolve program {program_id}. {err:?}");
+            Err(query_failure(err, "Failed to resolve the program on chain"))
+        }
+    }
+}
+
+/// Checks that the code to be verified is uploaded to the network
+pub async fn ensure_code_onchain(
+    client: &dyn ChainClient,
+    code_id: &str,
+) -> Result<(), ChainFailure> {
+    match client.check_code_onchain(code_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ChainFailure::permanent(
+            FailureKind::CodeNotOnchain,
+            "Code doesn't exist on chain",
+        )),
+        Err(err) => {
+            log::warn!("Failed to check code {code_id} on chain. {err:?}");
+            Err(query_failure(err, "Failed to check the code on chain"))
+        }
+    }
+}
+
+/// Original code the built one is compared with, `None` if it's not stored on chain
+pub async fn fetch_original_code(
+    client: &dyn ChainClient,
+    code_id: &str,
+) -> Result<Option<Vec<u8>>, ChainFailure> {
+    client.original_code(code_id).await.map_err(|err| {
+        log::warn!("Failed to fetch the original code {code_id}. {err:?}");
+        query_failure(err, "Failed to fetch the original code from chain")
+    })
+}
+
+/// Describes the first difference between the built and the on-chain code, `None` if they are equal
+pub fn compare_code(built: &[u8], onchain: Option<&[u8]>) -> Option<String> {
+    let Some(onchain) = onchain else {
+        return Some("Original code is not found on chain".into());
+    };
+
+    let offset = built
+        .iter()
+        .zip(onchain)
+        .position(|(b, o)| b != o)
+        .or((built.len() != onchain.len()).then(|| built.len().min(onchain.len())))?;
+
+    Some(format!(
+        "Built code differs from the on-chain code at byte {offset} (built: {} bytes, on-chain: {} bytes)",
+        built.len(),
+        onchain.len()
+    ))
+}
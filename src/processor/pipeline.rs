@@ -0,0 +1,334 @@
+use super::{
+    builder::{Build, ProjectBuilder},
+    docker::BuildError,
+    network_client::AppClients,
+    onchain::{
+        compare_code, ensure_code_onchain, fetch_original_code, resolve_code_id, ChainFailure,
+    },
+    store::VerificationStore,
+    wasm_diff::diff_wasm,
+};
+use crate::{
+    config::ProcessorConfig,
+    db::{BuildResult, CodeComparison, FailureKind, Verification},
+    util::{generate_code_id, retry_delay},
+};
+use anyhow::{bail, Result};
+use bollard::errors::Error as DockerError;
+use std::{fmt, time::SystemTime};
+
+/// Failure that may not happen again, e.g. a network or a Docker daemon error.
+/// Verifications failed with it are retried
+#[derive(Debug)]
+struct TransientError {
+    /// Kind of the failure once the attempts are exhausted
+    kind: FailureKind,
+    message: String,
+}
+
+impl TransientError {
+    fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for TransientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TransientError {}
+
+/// Failure already recorded on the verification, ends the processing without further updates
+#[derive(Debug)]
+struct RecordedFailure(String);
+
+impl fmt::Display for RecordedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RecordedFailure {}
+
+/// Processes the verification: resolves its program, checks its code on chain, builds the project
+/// and compares the built code with the original one. Verifications failed with a transient error
+/// are requeued until their attempts are exhausted, other unexpected errors fail them
+pub async fn process_verification(
+    store: &dyn VerificationStore,
+    builder: &dyn ProjectBuilder,
+    clients: &AppClients,
+    config: &ProcessorConfig,
+    verif: Verification,
+) {
+    let id = verif.id.clone();
+    let attempts = verif.attempts;
+
+    let result = async {
+        let verif = resolve_program(store, clients, verif).await?;
+
+        if let Err(err) = store.start(&verif).await {
+            log::warn!("{}: {:?}", &id, err);
+            return Ok(());
+        }
+
+        check_code_onchain(store, clients, &verif).await?;
+        build_and_verify(store, builder, clients, verif).await
+    }
+    .await;
+
+    if let Err(err) = result {
+        log::error!("{}: {:?}", &id, err);
+
+        if let Some(transient) = err.downcast_ref::<TransientError>() {
+            let TransientError { kind, message } = transient;
+            if let Err(err) =
+                retry_or_fail(store, &id, attempts, *kind, message.clone(), config).await
+            {
+                log::error!("{}: {:?}", &id, err);
+            }
+        } else if !err.is::<RecordedFailure>() {
+            // Otherwise the verification would stay in progress
+            if let Err(err) = store
+                .fail(
+                    &id,
+                    FailureKind::Internal,
+                    "Internal error while processing the verification".into(),
+                )
+                .await
+            {
+                log::error!("{}: {:?}", &id, err);
+            }
+        }
+    }
+}
+
+/// Fails the verification, unless the failure is transient and the verification is retried.
+/// Returns the error to end the processing with
+async fn handle_chain_failure(
+    store: &dyn VerificationStore,
+    verif_id: &str,
+    failure: ChainFailure,
+) -> anyhow::Error {
+    let ChainFailure {
+        kind,
+        reason,
+        transient,
+    } = failure;
+
+    if transient {
+        return TransientError::new(kind, reason).into();
+    }
+
+    match store.fail(verif_id, kind, reason.clone()).await {
+        Ok(()) => RecordedFailure(reason).into(),
+        Err(err) => err,
+    }
+}
+
+/// Requeues the verification after a transient failure with an exponential backoff,
+/// failing it once its attempts are exhausted
+async fn retry_or_fail(
+    store: &dyn VerificationStore,
+    verif_id: &str,
+    attempts: i32,
+    kind: FailureKind,
+    reason: String,
+    config: &ProcessorConfig,
+) -> Result<()> {
+    // Including the failed one
+    let attempts = attempts + 1;
+
+    let Some(delay) = retry_delay(config.retry_base_secs, config.max_attempts, attempts) else {
+        return store.fail(verif_id, kind, reason).await;
+    };
+    log::info!("{verif_id}: retrying in {} seconds", delay.as_secs());
+
+    store
+        .requeue(verif_id, kind, reason, SystemTime::now() + delay)
+        .await
+}
+
+/// Resolves the code ID of the requested program and records the program to code mapping
+async fn resolve_program(
+    store: &dyn VerificationStore,
+    clients: &AppClients,
+    mut verif: Verification,
+) -> Result<Verification> {
+    let (None, Some(program_id)) = (&verif.code_id, verif.program_id.clone()) else {
+        return Ok(verif);
+    };
+
+    let Ok(client) = clients.get(&verif.network) else {
+        store
+            .fail(
+                &verif.id,
+                FailureKind::Internal,
+                "Unsupported network".into(),
+            )
+            .await?;
+        bail!(RecordedFailure("Unsupported network".into()));
+    };
+
+    let code_id = match resolve_code_id(client.as_ref(), &program_id).await {
+        Ok(code_id) => code_id,
+        Err(failure) => return Err(handle_chain_failure(store, &verif.id, failure).await),
+    };
+
+    log::info!("{}: program {program_id} runs code {code_id}", &verif.id);
+
+    store
+        .set_program_code(&verif, &program_id, &code_id)
+        .await?;
+    verif.code_id = Some(code_id);
+
+    Ok(verif)
+}
+
+async fn check_code_onchain(
+    store: &dyn VerificationStore,
+    clients: &AppClients,
+    verif: &Verification,
+) -> Result<()> {
+    let Ok(client) = clients.get(&verif.network) else {
+        store
+            .fail(
+                &verif.id,
+                FailureKind::Internal,
+                "Unsupported network".into(),
+            )
+            .await?;
+        bail!(RecordedFailure("Unsupported network".into()));
+    };
+
+    if let Err(failure) = ensure_code_onchain(client.as_ref(), verif.code_id()).await {
+        return Err(handle_chain_failure(store, &verif.id, failure).await);
+    }
+
+    Ok(())
+}
+
+async fn build_and_verify(
+    store: &dyn VerificationStore,
+    builder: &dyn ProjectBuilder,
+    clients: &AppClients,
+    verif: Verification,
+) -> Result<()> {
+    let Build {
+        artifacts,
+        commit_sha,
+    } = builder.build(&verif).await;
+
+    if let Some(commit_sha) = &commit_sha {
+        store.set_commit_sha(&verif.id, commit_sha).await?;
+        log::info!("{}: commit {} built", &verif.id, commit_sha);
+    }
+
+    let artifacts = match artifacts {
+        Ok(artifacts) => artifacts,
+        Err(err) => {
+            // Details of internal errors are only logged
+            let (kind, err_msg, is_transient) = match err.downcast_ref::<BuildError>() {
+                Some(build_err) => (
+                    build_err.kind(),
+                    build_err.to_string(),
+                    build_err.is_transient(),
+                ),
+                None => (
+                    FailureKind::Internal,
+                    "Internal error while building the project".to_string(),
+                    err.downcast_ref::<DockerError>().is_some(),
+                ),
+            };
+            if is_transient {
+                return Err(err.context(TransientError::new(kind, err_msg)));
+            }
+            store.fail(&verif.id, kind, err_msg.clone()).await?;
+            return Err(err.context(RecordedFailure(err_msg)));
+        }
+    };
+
+    let onchain_code =
+        match fetch_original_code(clients.get(&verif.network)?.as_ref(), verif.code_id()).await {
+            Ok(onchain_code) => onchain_code,
+            Err(failure) => bail!(TransientError::new(failure.kind, failure.reason)),
+        };
+
+    store
+        .set_build_result(
+            &verif.id,
+            BuildResult {
+                calculated_code_id: Some(artifacts.code_id.clone()),
+                wasm_name: Some(artifacts.wasm_name.clone()),
+                artifact_sizes: Some(serde_json::to_value(&artifacts.sizes)?),
+            },
+        )
+        .await?;
+    log::info!("{}: calculated code ID {}", &verif.id, &artifacts.code_id);
+
+    let difference = compare_code(&artifacts.code, onchain_code.as_deref());
+    let is_mismatch = artifacts.code_id != verif.code_id() || difference.is_some();
+
+    let mut diff_report = None;
+
+    if is_mismatch {
+        if let Err(err) = builder.keep_artifact(&verif.id, &artifacts.code) {
+            log::error!("{}: Failed to keep built code. {err:?}", &verif.id);
+        }
+
+        if let Some(onchain_code) = &onchain_code {
+            match diff_wasm(&artifacts.code, onchain_code) {
+                Ok(report) => diff_report = Some(serde_json::to_value(report)?),
+                Err(err) => log::error!("{}: Failed to compare codes. {err:?}", &verif.id),
+            }
+        }
+    }
+
+    store
+        .set_code_comparison(
+            &verif.id,
+            CodeComparison {
+                built_code_len: Some(artifacts.code.len() as i32),
+                built_code_hash: Some(artifacts.code_id.clone()),
+                onchain_code_len: onchain_code.as_ref().map(|code| code.len() as i32),
+                onchain_code_hash: onchain_code.as_deref().map(generate_code_id),
+                diff_report,
+            },
+        )
+        .await?;
+
+    if artifacts.code_id != verif.code_id() {
+        store
+            .fail(
+                &verif.id,
+                FailureKind::CodeMismatch,
+                "Code ID mismatch".into(),
+            )
+            .await?;
+        bail!(RecordedFailure(format!(
+            "Code ID mismatch. Provided: {}. Calculated: {}",
+            verif.code_id(),
+            &artifacts.code_id,
+        )));
+    } else if let Some(difference) = difference {
+        let kind = match onchain_code {
+            Some(_) => FailureKind::CodeMismatch,
+            None => FailureKind::CodeNotOnchain,
+        };
+        store.fail(&verif.id, kind, difference.clone()).await?;
+        bail!(RecordedFailure(difference));
+    }
+
+    // Code of a verification cancelled while finishing the build isn't saved
+    if !store.complete(&verif, artifacts, commit_sha).await? {
+        log::warn!("{}: verification cancelled", &verif.id);
+        return Ok(());
+    }
+    log::info!("{}: verification completed", &verif.id);
+
+    Ok(())
+}
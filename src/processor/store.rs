@@ -0,0 +1,382 @@
+use crate::{
+    common::Pool,
+    db::{
+        BuildResult, Code, CodeComparison, FailureKind, Idl, Program, Verification,
+        VerificationStatus,
+    },
+    util::hash_idl,
+};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use diesel::{Connection, PgConnection};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use super::builder::BuildArtifacts;
+
+/// Records the progress of verifications processed by the pipeline
+#[async_trait]
+pub trait VerificationStore: Send + Sync {
+    /// Records the code ID the requested program runs
+    async fn set_program_code(
+        &self,
+        verif: &Verification,
+        program_id: &str,
+        code_id: &str,
+    ) -> Result<()>;
+
+    /// Marks the verification in progress. Fails if it can't be built now:
+    /// it's cancelled, its code is already verified or another verification of the code is in progress
+    async fn start(&self, verif: &Verification) -> Result<()>;
+
+    /// Fails the verification with the message shown to users
+    async fn fail(&self, verif_id: &str, kind: FailureKind, reason: String) -> Result<()>;
+
+    /// Returns the verification to the queue after a transient failure
+    async fn requeue(
+        &self,
+        verif_id: &str,
+        kind: FailureKind,
+        reason: String,
+        next_attempt_at: SystemTime,
+    ) -> Result<()>;
+
+    async fn set_commit_sha(&self, verif_id: &str, commit_sha: &str) -> Result<()>;
+
+    async fn set_build_result(&self, verif_id: &str, result: BuildResult) -> Result<()>;
+
+    async fn set_code_comparison(&self, verif_id: &str, comparison: CodeComparison) -> Result<()>;
+
+    /// Marks the verification verified and saves its code and IDL.
+    /// Returns `false` if the verification was cancelled while finishing the build
+    async fn complete(
+        &self,
+        verif: &Verification,
+        artifacts: BuildArtifacts,
+        commit_sha: Option<String>,
+    ) -> Result<bool>;
+}
+
+/// Runs the query on a pooled connection without blocking the runtime
+async fn run_blocking<T: Send + 'static>(
+    pool: &Pool,
+    query: impl FnOnce(&mut PgConnection) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().expect("Failed to get connection");
+        query(&mut conn)
+    })
+    .await?
+}
+
+#[async_trait]
+impl VerificationStore for Pool {
+    async fn set_program_code(
+        &self,
+        verif: &Verification,
+        program_id: &str,
+        code_id: &str,
+    ) -> Result<()> {
+        let (verif_id, network) = (verif.id.clone(), verif.network.clone());
+        let (program_id, code_id) = (program_id.to_string(), code_id.to_string());
+
+        run_blocking(self, move |conn| {
+            Verification::set_code_id(conn, &verif_id, &code_id)?;
+            Program::save(conn, &program_id, &code_id, &network)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn start(&self, verif: &Verification) -> Result<()> {
+        let verif = verif.clone();
+
+        run_blocking(self, move |conn| {
+            if Verification::update(conn, &verif.id, VerificationStatus::InProgress)? == 0 {
+                bail!("Verification cancelled");
+            }
+
+            // Check if the code is already verified or the verification is in progress
+            if Code::get(conn, verif.code_id()).is_some() {
+                Verification::update(conn, &verif.id, VerificationStatus::Verified)?;
+                Err(anyhow!("Code already verified"))
+            } else if Verification::is_verification_in_progress(conn, verif.code_id(), &verif.id) {
+                Verification::update(conn, &verif.id, VerificationStatus::Pending)?;
+                Err(anyhow!("Verification in progress"))
+            } else {
+                Ok(())
+            }
+        })
+        .await
+    }
+
+    async fn fail(&self, verif_id: &str, kind: FailureKind, reason: String) -> Result<()> {
+        let verif_id = verif_id.to_string();
+
+        run_blocking(self, move |conn| {
+            Verification::fail(conn, &verif_id, kind, reason)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn requeue(
+        &self,
+        verif_id: &str,
+        kind: FailureKind,
+        reason: String,
+        next_attempt_at: SystemTime,
+    ) -> Result<()> {
+        let verif_id = verif_id.to_string();
+
+        run_blocking(self, move |conn| {
+            Verification::requeue(conn, &verif_id, kind, reason, next_attempt_at)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_commit_sha(&self, verif_id: &str, commit_sha: &str) -> Result<()> {
+        let (verif_id, commit_sha) = (verif_id.to_string(), commit_sha.to_string());
+
+        run_blocking(self, move |conn| {
+            Verification::set_commit_sha(conn, &verif_id, &commit_sha)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_build_result(&self, verif_id: &str, result: BuildResult) -> Result<()> {
+        let verif_id = verif_id.to_string();
+
+        run_blocking(self, move |conn| {
+            Verification::set_build_result(conn, &verif_id, &result)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_code_comparison(&self, verif_id: &str, comparison: CodeComparison) -> Result<()> {
+        let verif_id = verif_id.to_string();
+
+        run_blocking(self, move |conn| {
+            Verification::set_code_comparison(conn, &verif_id, &comparison)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn complete(
+        &self,
+        verif: &Verification,
+        artifacts: BuildArtifacts,
+        commit_sha: Option<String>,
+    ) -> Result<bool> {
+        let verif = verif.clone();
+
+        run_blocking(self, move |conn| {
+            conn.transaction(|conn| {
+                if Verification::update(conn, &verif.id, VerificationStatus::Verified)? == 0 {
+                    return Ok(false);
+                }
+
+                let mut idl_hash: Option<String> = None;
+
+                if let Some(idl) = artifacts.idl {
+                    idl_hash = Some(hash_idl(&idl));
+                    Idl::save(conn, idl_hash.as_ref().unwrap(), idl)?;
+                    log::info!("{}: idl saved", &verif.id);
+                }
+                Code::new(
+                    conn,
+                    verif.code_id().to_string(),
+                    verif.repo_link.clone(),
+                    artifacts.name,
+                    idl_hash,
+                    commit_sha,
+                )?;
+                log::info!("{}: code meta saved", &verif.id);
+
+                Ok(true)
+            })
+        })
+        .await
+    }
+}
+
+/// Store keeping verifications and verified codes in memory, allows to run the pipeline offline
+#[derive(Default)]
+pub struct MemoryStore {
+    verifications: Mutex<HashMap<String, Verification>>,
+    codes: Mutex<HashMap<String, Code>>,
+}
+
+impl MemoryStore {
+    /// Adds the verification as if it was requested
+    pub fn with_verification(self, verif: Verification) -> Self {
+        self.verifications().insert(verif.id.clone(), verif);
+        self
+    }
+
+    /// Current state of the verification
+    pub fn verification(&self, verif_id: &str) -> Option<Verification> {
+        self.verifications().get(verif_id).cloned()
+    }
+
+    /// Code saved by a successful verification
+    pub fn code(&self, code_id: &str) -> Option<Code> {
+        self.codes.lock().unwrap().get(code_id).cloned()
+    }
+
+    fn verifications(&self) -> MutexGuard<'_, HashMap<String, Verification>> {
+        self.verifications.lock().unwrap()
+    }
+
+    /// Applies the update unless the verification is cancelled, like the database queries do.
+    /// Returns whether the verification was updated
+    fn update(&self, verif_id: &str, update: impl FnOnce(&mut Verification)) -> Result<bool> {
+        let mut verifications = self.verifications();
+        let verif = verifications
+            .get_mut(verif_id)
+            .ok_or_else(|| anyhow!("Verification {verif_id} doesn't exist"))?;
+
+        if verif.status == VerificationStatus::Cancelled {
+            return Ok(false);
+        }
+
+        update(verif);
+        Ok(true)
+    }
+
+    fn set_status(&self, verif_id: &str, status: VerificationStatus) -> Result<bool> {
+        self.update(verif_id, |verif| {
+            verif.status = status;
+            verif.failure_kind = None;
+            verif.failed_reason = None;
+        })
+    }
+}
+
+#[async_trait]
+impl VerificationStore for MemoryStore {
+    async fn set_program_code(
+        &self,
+        verif: &Verification,
+        _program_id: &str,
+        code_id: &str,
+    ) -> Result<()> {
+        let mut verifications = self.verifications();
+        if let Some(verif) = verifications.get_mut(&verif.id) {
+            verif.code_id = Some(code_id.to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn start(&self, verif: &Verification) -> Result<()> {
+        if !self.set_status(&verif.id, VerificationStatus::InProgress)? {
+            bail!("Verification cancelled");
+        }
+
+        let in_progress = self.verifications().values().any(|other| {
+            other.id != verif.id
+                && other.code_id == verif.code_id
+                && other.status == VerificationStatus::InProgress
+        });
+
+        if self.code(verif.code_id()).is_some() {
+            self.set_status(&verif.id, VerificationStatus::Verified)?;
+            Err(anyhow!("Code already verified"))
+        } else if in_progress {
+            self.set_status(&verif.id, VerificationStatus::Pending)?;
+            Err(anyhow!("Verification in progress"))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn fail(&self, verif_id: &str, kind: FailureKind, reason: String) -> Result<()> {
+        self.update(verif_id, |verif| {
+            verif.status = VerificationStatus::Failed;
+            verif.failure_kind = Some(kind);
+            verif.failed_reason = Some(reason);
+        })?;
+
+        Ok(())
+    }
+
+    async fn requeue(
+        &self,
+        verif_id: &str,
+        kind: FailureKind,
+        reason: String,
+        next_attempt_at: SystemTime,
+    ) -> Result<()> {
+        self.update(verif_id, |verif| {
+            verif.status = VerificationStatus::Pending;
+            verif.failure_kind = Some(kind);
+            verif.failed_reason = Some(reason);
+            verif.attempts += 1;
+            verif.next_attempt_at = Some(next_attempt_at);
+        })?;
+
+        Ok(())
+    }
+
+    async fn set_commit_sha(&self, verif_id: &str, commit_sha: &str) -> Result<()> {
+        self.update(verif_id, |verif| {
+            verif.commit_sha = Some(commit_sha.to_string())
+        })?;
+
+        Ok(())
+    }
+
+    async fn set_build_result(&self, verif_id: &str, result: BuildResult) -> Result<()> {
+        self.update(verif_id, |verif| {
+            verif.calculated_code_id = result.calculated_code_id;
+            verif.wasm_name = result.wasm_name;
+            verif.artifact_sizes = result.artifact_sizes;
+        })?;
+
+        Ok(())
+    }
+
+    async fn set_code_comparison(&self, verif_id: &str, comparison: CodeComparison) -> Result<()> {
+        self.update(verif_id, |verif| {
+            verif.built_code_len = comparison.built_code_len;
+            verif.built_code_hash = comparison.built_code_hash;
+            verif.onchain_code_len = comparison.onchain_code_len;
+            verif.onchain_code_hash = comparison.onchain_code_hash;
+            verif.diff_report = comparison.diff_report;
+        })?;
+
+        Ok(())
+    }
+
+    async fn complete(
+        &self,
+        verif: &Verification,
+        artifacts: BuildArtifacts,
+        commit_sha: Option<String>,
+    ) -> Result<bool> {
+        if !self.set_status(&verif.id, VerificationStatus::Verified)? {
+            return Ok(false);
+        }
+
+        let code = Code {
+            id: verif.code_id().to_string(),
+            idl_hash: artifacts.idl.as_deref().map(hash_idl),
+            name: artifacts.name,
+            repo_link: verif.repo_link.clone(),
+            commit_sha,
+        };
+        self.codes.lock().unwrap().insert(code.id.clone(), code);
+
+        Ok(true)
+    }
+}
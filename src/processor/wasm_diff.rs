@@ -0,0 +1,172 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+use wasmparser::{Parser, Payload};
+
+/// Section-level comparison of the built wasm with the on-chain code
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct WasmDiffReport {
+    /// Sections whose contents differ
+    pub sections: Vec<SectionDiff>,
+    /// Exported items present only in one of the codes
+    pub exports: SetDiff,
+    /// Imported items (`module.name`) present only in one of the codes
+    pub imports: SetDiff,
+    /// Functions defined in the codes
+    pub functions: FunctionsDiff,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct SectionDiff {
+    /// Name of the section, custom sections are prefixed with `custom:`
+    pub name: String,
+    /// Size of the section in the built code, if present
+    pub built_size: Option<usize>,
+    /// Size of the section in the on-chain code, if present
+    pub onchain_size: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default)]
+pub struct SetDiff {
+    pub only_built: Vec<String>,
+    pub only_onchain: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct FunctionsDiff {
+    pub built_count: usize,
+    pub onchain_count: usize,
+    /// Total size of function bodies in the built code
+    pub built_size: usize,
+    /// Total size of function bodies in the on-chain code
+    pub onchain_size: usize,
+    /// Number of functions whose bodies differ at the same index
+    pub differing_bodies: usize,
+}
+
+#[derive(Default)]
+struct WasmSummary<'a> {
+    sections: BTreeMap<String, &'a [u8]>,
+    section_order: Vec<String>,
+    exports: BTreeSet<String>,
+    imports: BTreeSet<String>,
+    bodies: Vec<&'a [u8]>,
+}
+
+fn section_name(id: u8) -> String {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data_count",
+        13 => "tag",
+        _ => return format!("unknown:{id}"),
+    }
+    .to_string()
+}
+
+fn summarize(code: &[u8]) -> Result<WasmSummary<'_>> {
+    let mut summary = WasmSummary::default();
+
+    for payload in Parser::new(0).parse_all(code) {
+        let payload = payload?;
+
+        if let Some((id, range)) = payload.as_section() {
+            let mut name = match &payload {
+                Payload::CustomSection(reader) => format!("custom:{}", reader.name()),
+                _ => section_name(id),
+            };
+            // Custom sections may repeat
+            if summary.sections.contains_key(&name) {
+                name = format!("{name}#{}", summary.section_order.len());
+            }
+            summary.sections.insert(name.clone(), &code[range]);
+            summary.section_order.push(name);
+        }
+
+        match payload {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import?;
+                    summary
+                        .imports
+                        .insert(format!("{}.{}", import.module, import.name));
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    summary.exports.insert(export?.name.to_string());
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                summary.bodies.push(&code[body.range()]);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(summary)
+}
+
+fn set_diff(built: &BTreeSet<String>, onchain: &BTreeSet<String>) -> SetDiff {
+    SetDiff {
+        only_built: built.difference(onchain).cloned().collect(),
+        only_onchain: onchain.difference(built).cloned().collect(),
+    }
+}
+
+/// Compares sections, exports, imports and functions of the codes
+pub fn diff_wasm(built: &[u8], onchain: &[u8]) -> Result<WasmDiffReport> {
+    let built = summarize(built)?;
+    let onchain = summarize(onchain)?;
+
+    let section_names = built.section_order.iter().chain(
+        onchain
+            .section_order
+            .iter()
+            .filter(|name| !built.sections.contains_key(*name)),
+    );
+
+    let sections = section_names
+        .filter_map(|name| {
+            let built_section = built.sections.get(name);
+            let onchain_section = onchain.sections.get(name);
+
+            (built_section != onchain_section).then(|| SectionDiff {
+                name: name.clone(),
+                built_size: built_section.map(|s| s.len()),
+                onchain_size: onchain_section.map(|s| s.len()),
+            })
+        })
+        .collect();
+
+    let differing_bodies = built
+        .bodies
+        .iter()
+        .zip(&onchain.bodies)
+        .filter(|(b, o)| b != o)
+        .count()
+        + built.bodies.len().abs_diff(onchain.bodies.len());
+
+    Ok(WasmDiffReport {
+        sections,
+        exports: set_diff(&built.exports, &onchain.exports),
+        imports: set_diff(&built.imports, &onchain.imports),
+        functions: FunctionsDiff {
+            built_count: built.bodies.len(),
+            onchain_count: onchain.bodies.len(),
+            built_size: built.bodies.iter().map(|b| b.len()).sum(),
+            onchain_size: onchain.bodies.iter().map(|b| b.len()).sum(),
+            differing_bodies,
+        },
+    })
+}
@@ -0,0 +1,178 @@
+use crate::{
+    common::Pool,
+    config::{Config, WebhookConfig},
+    db::{FailureKind, Verification, WebhookDelivery},
+    util::{is_internal_ip, retry_delay, validate_callback_url},
+};
+use anyhow::{bail, Result};
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{net::SocketAddr, sync::Arc, time::SystemTime};
+use tokio::time;
+
+/// Header with the hex encoded HMAC-SHA256 of the request body, prefixed with `sha256=`
+pub const SIGNATURE_HEADER: &str = "X-Verifier-Signature";
+
+const CHECK_INTERVAL: time::Duration = time::Duration::from_secs(5);
+const DELIVERIES_PER_CHECK: i64 = 100;
+
+/// Body of the callback request
+#[derive(Serialize)]
+struct WebhookPayload {
+    id: String,
+    status: String,
+    failure_kind: Option<FailureKind>,
+    failed_reason: Option<String>,
+    code_id: Option<String>,
+    program_id: Option<String>,
+    network: String,
+    commit_sha: Option<String>,
+}
+
+impl From<Verification> for WebhookPayload {
+    fn from(verif: Verification) -> Self {
+        Self {
+            id: verif.id,
+            status: verif.status.into(),
+            failure_kind: verif.failure_kind,
+            failed_reason: verif.failed_reason,
+            code_id: verif.code_id,
+            program_id: verif.program_id,
+            network: verif.network,
+            commit_sha: verif.commit_sha,
+        }
+    }
+}
+
+/// Signature of the body sent in [`SIGNATURE_HEADER`]
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Resolver of callback hosts that refuses internal addresses, so a host can't be
+/// pointed at the verifier's network after its callback URL was accepted
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+
+            if addrs.is_empty() || addrs.iter().any(|addr| is_internal_ip(addr.ip())) {
+                return Err(format!("{} resolves to an internal address", name.as_str()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    secret: &str,
+    url: &str,
+    verif: Verification,
+) -> Result<()> {
+    validate_callback_url(url).await?;
+
+    let body = serde_json::to_vec(&WebhookPayload::from(verif))?;
+
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign_payload(secret, &body))
+        .body(body)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        bail!("Callback responded with {}", response.status());
+    }
+
+    Ok(())
+}
+
+async fn send_due(
+    pool: &Arc<Pool>,
+    config: &WebhookConfig,
+    client: &reqwest::Client,
+    secret: &str,
+) {
+    let due = {
+        let pool = pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get().expect("Failed to get connection");
+            WebhookDelivery::get_due(&mut conn, DELIVERIES_PER_CHECK)
+        })
+        .await
+    };
+
+    let due = match due {
+        Ok(Ok(due)) => due,
+        result => {
+            log::error!("Failed to get due callbacks. {result:?}");
+            return;
+        }
+    };
+
+    for (delivery, verif) in due {
+        let id = delivery.verification_id;
+
+        let (error, next_attempt_at) = match send(client, secret, &delivery.url, verif).await {
+            Ok(()) => {
+                log::info!("{id}: callback delivered");
+                (None, None)
+            }
+            Err(error) => {
+                let attempts = delivery.attempts + 1;
+                let next_attempt_at =
+                    retry_delay(config.retry_base_secs, config.max_attempts, attempts)
+                        .map(|delay| SystemTime::now() + delay);
+                log::warn!("{id}: callback attempt {attempts} failed. {error:?}");
+                (Some(error.to_string()), next_attempt_at)
+            }
+        };
+
+        let pool = pool.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get().expect("Failed to get connection");
+            WebhookDelivery::record_attempt(&mut conn, &id, error, next_attempt_at)
+        })
+        .await;
+
+        if !matches!(result, Ok(Ok(_))) {
+            log::error!("Failed to record callback attempt. {result:?}");
+        }
+    }
+}
+
+/// Delivers callbacks of finished verifications, retrying failed ones with backoff
+pub async fn run_webhooks(pool: Arc<Pool>, config: Arc<Config>) -> Result<()> {
+    let config = &config.webhooks;
+
+    let Some(secret) = &config.secret else {
+        log::warn!("WEBHOOK_SECRET is not set, callbacks are disabled");
+        return Ok(());
+    };
+
+    let client = reqwest::Client::builder()
+        .timeout(config.timeout())
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()?;
+
+    let mut check_interval = time::interval(CHECK_INTERVAL);
+    check_interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+    loop {
+        check_interval.tick().await;
+        send_due(&pool, config, &client, secret).await;
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    common::Pool,
+    config::Config,
+    db::{ApiKey, ApiScope},
+    util::blake2_hex,
+};
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Client that made the request with a valid API key
+#[derive(Clone, Debug)]
+pub struct Caller {
+    /// ID of the API key, `None` for the admin token
+    pub key_id: Option<String>,
+    pub scopes: Vec<ApiScope>,
+}
+
+/// Resolves API keys of requests and the scopes allowed without a key
+pub struct Authenticator {
+    pool: Arc<Pool>,
+    admin_token: Option<String>,
+    anonymous_scopes: Vec<ApiScope>,
+}
+
+impl Authenticator {
+    pub fn new(pool: Arc<Pool>, config: &Config) -> Self {
+        let mut anonymous_scopes = Vec::new();
+        if config.auth.anonymous_read {
+            anonymous_scopes.push(ApiScope::Read);
+        }
+        if config.auth.anonymous_submit {
+            anonymous_scopes.push(ApiScope::Submit);
+        }
+
+        Self {
+            pool,
+            admin_token: config.server.admin_token.clone(),
+            anonymous_scopes,
+        }
+    }
+
+    async fn caller(&self, token: &str) -> Option<Caller> {
+        if self.admin_token.as_deref() == Some(token) {
+            return Some(Caller {
+                key_id: None,
+                scopes: vec![ApiScope::Read, ApiScope::Submit, ApiScope::Admin],
+            });
+        }
+
+        let pool = self.pool.clone();
+        let key_hash = blake2_hex(token.as_bytes());
+
+        let api_key = tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get().expect("Failed to get connection");
+            ApiKey::get_active(&mut conn, &key_hash)
+        })
+        .await
+        .ok()??;
+
+        Some(Caller {
+            scopes: api_key.scopes(),
+            key_id: Some(api_key.id),
+        })
+    }
+}
+
+/// Allows requests with `Authorization: Bearer <API key>` header if the key has the scope,
+/// and requests without the header if the scope is allowed anonymously.
+/// The caller is added to the request extensions
+pub async fn authorize(
+    State((authenticator, scope)): State<(Arc<Authenticator>, ApiScope)>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let Some(token) = token else {
+        if authenticator.anonymous_scopes.contains(&scope) {
+            return Ok(next.run(req).await);
+        }
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let Some(caller) = authenticator.caller(token).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    if !caller.scopes.contains(&scope) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    req.extensions_mut().insert(caller);
+
+    Ok(next.run(req).await)
+}
@@ -1,13 +1,39 @@
+use super::rate_limit::RateLimited;
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use std::fmt;
+
+/// Idempotency key of the request was used before with a different request
+#[derive(Debug)]
+pub struct IdempotencyKeyReused;
+
+impl fmt::Display for IdempotencyKeyReused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Idempotency key was already used with a different request"
+        )
+    }
+}
+
+impl std::error::Error for IdempotencyKeyReused {}
 
 pub struct AppError(anyhow::Error);
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", self.0)).into_response()
+        let error = match self.0.downcast::<RateLimited>() {
+            Ok(limited) => return limited.into_response(),
+            Err(error) => error,
+        };
+
+        if error.is::<IdempotencyKeyReused>() {
+            return (StatusCode::UNPROCESSABLE_ENTITY, error.to_string()).into_response();
+        }
+
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", error)).into_response()
     }
 }
 
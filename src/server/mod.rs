@@ -1,14 +1,22 @@
-use crate::{common::Pool, db};
+use crate::{common::Pool, config::Config, db, AppClients};
+use auth::Authenticator;
 use axum::{
-    routing::{get, post},
+    extract::FromRef,
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
-use routes::{code, idl, verify, version};
-use std::sync::Arc;
+use db::ApiScope;
+use rate_limit::RateLimiter;
+use routes::code::ProgramScans;
+use routes::{admin, code, idl, network, program, verify, version};
+use std::{net::SocketAddr, sync::Arc};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+pub mod auth;
 pub mod error;
+pub mod rate_limit;
 mod routes;
 pub mod types;
 
@@ -17,40 +25,166 @@ pub mod types;
     paths(
         verify::verify,
         verify::status,
+        verify::cancel,
+        verify::retry,
+        verify::verifications,
+        verify::logs,
+        verify::logs_stream,
+        verify::artifact,
         code::code,
         code::codes,
+        code::code_programs,
         idl::idl,
+        network::networks,
+        program::program,
         version::supported_versions,
-        version::version
+        version::version,
+        admin::register_version,
+        admin::create_api_key,
+        admin::revoke_api_key
     ),
     components(schemas(
         types::VerifyRequest,
         types::VerifyResponse,
         db::Code,
         db::Idl,
-        types::StatusResponse
+        db::SailsVersion,
+        db::ImageStatus,
+        db::FailureKind,
+        types::StatusResponse,
+        types::VerificationsResponse,
+        types::LogsResponse,
+        types::RegisterVersionRequest,
+        types::CreateApiKeyRequest,
+        types::CreateApiKeyResponse,
+        db::ApiScope,
+        types::NetworkResponse,
+        types::ProgramResponse,
+        types::CodeProgramsResponse,
+        crate::WasmDiffReport,
+        crate::SectionDiff,
+        crate::SetDiff,
+        crate::FunctionsDiff
     ))
 )]
 pub struct ApiDoc;
 
-pub async fn run_server(pool: Arc<Pool>) {
-    let app = Router::new()
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: Arc<Pool>,
+    pub config: Arc<Config>,
+    pub clients: Arc<AppClients>,
+    pub program_scans: Arc<ProgramScans>,
+}
+
+impl FromRef<AppState> for Arc<Pool> {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<AppClients> {
+    fn from_ref(state: &AppState) -> Self {
+        state.clients.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ProgramScans> {
+    fn from_ref(state: &AppState) -> Self {
+        state.program_scans.clone()
+    }
+}
+
+pub async fn run_server(pool: Arc<Pool>, config: Arc<Config>, clients: Arc<AppClients>) {
+    let authenticator = Arc::new(Authenticator::new(pool.clone(), &config));
+
+    if config.server.admin_token.is_none() {
+        log::warn!("ADMIN_TOKEN is not set, admin routes require an API key with admin scope");
+    }
+
+    let admin = Router::new()
+        .route("/admin/versions", post(routes::admin::register_version))
+        .route(
+            "/admin/api_keys",
+            post(routes::admin::create_api_key).delete(routes::admin::revoke_api_key),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (authenticator.clone(), ApiScope::Admin),
+            auth::authorize,
+        ));
+
+    // The caller is resolved before rate limiting, so that API keys are limited separately
+    let submit = Router::new()
         .route("/verify", post(routes::verify::verify))
+        .route("/verify/retry", post(routes::verify::retry))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(RateLimiter::new(&config.rate_limit)),
+            rate_limit::limit,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (authenticator.clone(), ApiScope::Submit),
+            auth::authorize,
+        ));
+
+    // Cancelling doesn't create builds, so it isn't rate limited
+    let cancel = Router::new()
+        .route("/verify", delete(routes::verify::cancel))
+        .route_layer(middleware::from_fn_with_state(
+            (authenticator.clone(), ApiScope::Submit),
+            auth::authorize,
+        ));
+
+    let read = Router::new()
         .route("/verify/status", get(routes::verify::status))
+        .route("/verifications", get(routes::verify::verifications))
+        .route("/verify/logs", get(routes::verify::logs))
+        .route("/verify/logs/stream", get(routes::verify::logs_stream))
+        .route("/verify/artifact", get(routes::verify::artifact))
         .route("/code", get(routes::code::code))
         .route("/codes", get(routes::code::codes))
+        .route("/code/programs", get(routes::code::code_programs))
         .route("/idl", get(routes::idl::idl))
-        .route("/version", get(routes::version::version))
+        .route("/networks", get(routes::network::networks))
+        .route("/program", get(routes::program::program))
         .route(
             "/supported_versions",
             get(routes::version::supported_versions),
         )
-        .with_state(pool)
+        .route_layer(middleware::from_fn_with_state(
+            (authenticator, ApiScope::Read),
+            auth::authorize,
+        ));
+
+    let app = Router::new()
+        .route("/version", get(routes::version::version))
+        .merge(read)
+        .merge(submit)
+        .merge(cancel)
+        .merge(admin)
+        .with_state(AppState {
+            pool,
+            config: config.clone(),
+            clients,
+            program_scans: Default::default(),
+        })
         .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", ApiDoc::openapi()));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    let listener = tokio::net::TcpListener::bind(config.server.bind_addr)
+        .await
+        .unwrap();
 
     log::info!("listening on {}", listener.local_addr().unwrap());
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,200 @@
+use super::auth::Caller;
+use crate::config::RateLimitConfig;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Buckets of full clients are dropped once this many clients are tracked
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Request is rejected until `retry_after` passes
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Too many verification requests, retry in {} seconds",
+            self.retry_after.as_secs().max(1)
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, self.retry_after.as_secs().max(1).to_string())],
+            self.to_string(),
+        )
+            .into_response()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token buckets keyed by client. Every bucket holds up to `capacity` tokens,
+/// regaining one token per `refill` interval
+pub struct TokenBuckets {
+    capacity: f64,
+    refill: Duration,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl TokenBuckets {
+    pub fn new(capacity: u32, refill: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of the key.
+    /// Returns the time until a token is available if the bucket is empty
+    pub fn take(&self, key: &str, now: Instant) -> Result<(), RateLimited> {
+        let mut buckets = self.buckets.lock().expect("Rate limiter lock is poisoned");
+
+        if buckets.len() >= MAX_TRACKED_KEYS {
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(RateLimited {
+                retry_after: self.refill.mul_f64(1.0 - bucket.tokens),
+            })
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        (bucket.tokens + elapsed.as_secs_f64() / self.refill.as_secs_f64()).min(self.capacity)
+    }
+}
+
+/// Limits of verification requests per client IP or API key and per repository
+pub struct RateLimiter {
+    per_ip: TokenBuckets,
+    per_key: TokenBuckets,
+    per_repo: TokenBuckets,
+    trust_forwarded_for: bool,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            per_ip: TokenBuckets::new(config.ip_burst, config.ip_refill()),
+            per_key: TokenBuckets::new(config.key_burst, config.key_refill()),
+            per_repo: TokenBuckets::new(config.repo_burst, config.repo_refill()),
+            trust_forwarded_for: config.trust_forwarded_for,
+        }
+    }
+}
+
+/// Address of the client, taken from `X-Forwarded-For` if the proxy in front of the server is trusted
+pub fn client_ip(headers: &HeaderMap, peer: IpAddr, trust_forwarded_for: bool) -> IpAddr {
+    if !trust_forwarded_for {
+        return peer;
+    }
+
+    // The closest proxy appends the address of its client to the end of the list
+    headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok())
+        .unwrap_or(peer)
+}
+
+#[derive(Deserialize)]
+struct RepoLink {
+    repo_link: String,
+}
+
+/// Normalizes the link, so that variants of the same repository share a bucket
+fn repo_key(repo_link: &str) -> String {
+    let link = repo_link.trim().trim_end_matches('/');
+    link.strip_suffix(".git").unwrap_or(link).to_lowercase()
+}
+
+/// Takes tokens of the client IP, or of the API key if the request has one,
+/// and of the requested repository
+pub async fn limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let now = Instant::now();
+
+    let key_id = req
+        .extensions()
+        .get::<Caller>()
+        .and_then(|caller| caller.key_id.clone());
+
+    let client_limit = match key_id {
+        Some(key_id) => limiter.per_key.take(&key_id, now).inspect_err(|_| {
+            log::warn!("Verification requests of API key {key_id} are rate limited")
+        }),
+        None => {
+            let ip = client_ip(req.headers(), peer.ip(), limiter.trust_forwarded_for);
+            limiter
+                .per_ip
+                .take(&ip.to_string(), now)
+                .inspect_err(|_| log::warn!("Verification requests from {ip} are rate limited"))
+        }
+    };
+
+    if let Err(limited) = client_limit {
+        return limited.into_response();
+    }
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY_SIZE).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+
+    // Malformed requests are rejected by the handler
+    if let Ok(RepoLink { repo_link }) = serde_json::from_slice(&body) {
+        let key = repo_key(&repo_link);
+        if let Err(limited) = limiter.per_repo.take(&key, now) {
+            log::warn!("Verification requests of {key} are rate limited");
+            return limited.into_response();
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
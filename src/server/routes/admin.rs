@@ -0,0 +1,95 @@
+use crate::{
+    common::Pool,
+    config::Config,
+    db::{ApiKey, ImageStatus, SailsVersion},
+    prepare_version_image,
+    server::{
+        error::AppError,
+        types::{CreateApiKeyRequest, CreateApiKeyResponse, IdQueryParams, RegisterVersionRequest},
+    },
+    util::{blake2_hex, generate_api_key, generate_id, validate_version},
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+
+#[utoipa::path(post, path="/admin/versions", request_body=RegisterVersionRequest, responses(
+    (status = 202, description="Version accepted. Its verifier image is being built, the status is reported by /supported_versions"),
+    (status = 409, description="Version is already supported")
+))]
+pub async fn register_version(
+    State(pool): State<Arc<Pool>>,
+    State(config): State<Arc<Config>>,
+    Json(RegisterVersionRequest { version }): Json<RegisterVersionRequest>,
+) -> Result<StatusCode, AppError> {
+    validate_version(&version)?;
+
+    {
+        let conn = &mut pool.get().unwrap();
+
+        // Versions whose image failed to build can be registered again to retry
+        if let Some(v) = SailsVersion::get(conn, &version) {
+            if v.status != ImageStatus::Failed {
+                return Ok(StatusCode::CONFLICT);
+            }
+        }
+
+        SailsVersion::save(conn, &version)?;
+    }
+
+    tokio::spawn(prepare_version_image(pool, config, version));
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(post, path="/admin/api_keys", request_body=CreateApiKeyRequest, responses(
+    (status = 200, description="API key created. The key is returned only once", body=CreateApiKeyResponse)
+))]
+pub async fn create_api_key(
+    State(pool): State<Arc<Pool>>,
+    Json(CreateApiKeyRequest { name, scopes }): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreateApiKeyResponse>, AppError> {
+    if name.trim().is_empty() {
+        return Err(anyhow!("API key name must not be empty").into());
+    }
+    if scopes.is_empty() {
+        return Err(anyhow!("API key must have at least one scope").into());
+    }
+
+    let key = generate_api_key();
+    let api_key = ApiKey::save(
+        &mut pool.get().unwrap(),
+        &generate_id(),
+        &name,
+        &blake2_hex(key.as_bytes()),
+        &scopes,
+    )?;
+
+    log::info!("API key {} ({name}) created", &api_key.id);
+
+    Ok(Json(CreateApiKeyResponse {
+        id: api_key.id,
+        key,
+    }))
+}
+
+#[utoipa::path(delete, path="/admin/api_keys", params(IdQueryParams), responses(
+    (status = 204, description="API key revoked"),
+    (status = 404, description="API key doesn't exist or is already revoked")
+))]
+pub async fn revoke_api_key(
+    State(pool): State<Arc<Pool>>,
+    Query(params): Query<IdQueryParams>,
+) -> Result<StatusCode, AppError> {
+    if ApiKey::revoke(&mut pool.get().unwrap(), &params.id)? == 0 {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+
+    log::info!("API key {} revoked", &params.id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,5 +1,13 @@
-use crate::server::types::{CodeIdsQueryParams, CodesResponseEntry, IdQueryParams};
-use crate::{common::Pool, db::Code, util::validate_and_get_code_id};
+use crate::server::types::{
+    CodeIdsQueryParams, CodeProgramsResponse, CodesResponseEntry, IdQueryParams,
+};
+use crate::{
+    common::Pool,
+    config::Config,
+    db::{Code, CodePrograms, Program, Verification},
+    util::validate_and_get_code_id,
+    AppClients,
+};
 use axum::{
     extract::{Query, State},
     http::StatusCode,
@@ -7,9 +15,40 @@ use axum::{
 };
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::SystemTime,
 };
 
+type ScanLock = Arc<tokio::sync::Mutex<()>>;
+
+/// Program storage scans in progress, keyed by code ID and network.
+/// Concurrent requests of the same code wait for a single scan instead of starting their own
+#[derive(Default)]
+pub struct ProgramScans(Mutex<HashMap<(String, String), ScanLock>>);
+
+impl ProgramScans {
+    fn get(&self, code_id: &str, network: &str) -> ScanLock {
+        self.0
+            .lock()
+            .unwrap()
+            .entry((code_id.to_string(), network.to_string()))
+            .or_default()
+            .clone()
+    }
+
+    /// Forgets the scan if no other request is waiting for it
+    fn release(&self, code_id: &str, network: &str) {
+        let mut scans = self.0.lock().unwrap();
+        let key = (code_id.to_string(), network.to_string());
+        if scans
+            .get(&key)
+            .is_some_and(|scan| Arc::strong_count(scan) == 1)
+        {
+            scans.remove(&key);
+        }
+    }
+}
+
 #[utoipa::path(get, path="/codes", params(CodeIdsQueryParams), responses(
     (status = 200, description="Get list of codes by its ids", body=Vec<CodesResponseEntry>)
 ))]
@@ -87,3 +126,98 @@ pub async fn code(
         Err(StatusCode::NOT_FOUND)
     }
 }
+
+#[utoipa::path(get, path="/code/programs", params(IdQueryParams), responses(
+    (status = 200, description="Programs running the verified code", body=CodeProgramsResponse)
+))]
+pub async fn code_programs(
+    State(pool): State<Arc<Pool>>,
+    State(config): State<Arc<Config>>,
+    State(clients): State<Arc<AppClients>>,
+    State(scans): State<Arc<ProgramScans>>,
+    Query(params): Query<IdQueryParams>,
+) -> Result<Json<CodeProgramsResponse>, StatusCode> {
+    let Ok(code_id) = validate_and_get_code_id(&params.id) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let (network, cached) = {
+        let conn = &mut pool.get().unwrap();
+
+        let Some(network) = Verification::verified_network(conn, &code_id) else {
+            return Err(StatusCode::NOT_FOUND);
+        };
+        let cached = CodePrograms::get(conn, &code_id, &network);
+
+        (network, cached)
+    };
+
+    let is_fresh = |cached: &CodePrograms| {
+        let age = SystemTime::now()
+            .duration_since(cached.updated_at)
+            .unwrap_or_default();
+        age < config.server.programs_cache_ttl()
+    };
+
+    if let Some(cached) = cached.as_ref().filter(|cached| is_fresh(cached)) {
+        return Ok(Json(cached.into()));
+    }
+
+    let scan = scans.get(&code_id, &network);
+    let result = {
+        let _scan = scan.lock().await;
+
+        // The list may have been refreshed while waiting for another scan
+        let cached = CodePrograms::get(&mut pool.get().unwrap(), &code_id, &network);
+        match cached.as_ref().filter(|cached| is_fresh(cached)) {
+            Some(cached) => Ok(Json(cached.into())),
+            None => scan_programs(&pool, &clients, &code_id, &network, cached).await,
+        }
+    };
+    drop(scan);
+    scans.release(&code_id, &network);
+
+    result
+}
+
+/// Scans the network for the programs of the code and caches them
+async fn scan_programs(
+    pool: &Pool,
+    clients: &AppClients,
+    code_id: &str,
+    network: &str,
+    cached: Option<CodePrograms>,
+) -> Result<Json<CodeProgramsResponse>, StatusCode> {
+    let program_ids = match clients.get(network) {
+        Ok(client) => client.code_programs(code_id).await,
+        Err(error) => Err(error),
+    };
+
+    let program_ids = match program_ids {
+        Ok(program_ids) => program_ids,
+        Err(error) => {
+            log::error!("Failed to get programs of code {code_id}. {error:?}");
+            // Outdated list is better than none
+            return match cached {
+                Some(cached) => Ok(Json((&cached).into())),
+                None => Err(StatusCode::SERVICE_UNAVAILABLE),
+            };
+        }
+    };
+
+    let conn = &mut pool.get().unwrap();
+
+    for program_id in &program_ids {
+        if let Err(error) = Program::save(conn, program_id, code_id, network) {
+            log::error!("Failed to save program {program_id}. {error:?}");
+        }
+    }
+
+    match CodePrograms::save(conn, code_id, network, program_ids) {
+        Ok(programs) => Ok(Json((&programs).into())),
+        Err(error) => {
+            log::error!("Failed to save programs of code {code_id}. {error:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -1,4 +1,7 @@
+pub mod admin;
 pub mod code;
 pub mod idl;
+pub mod network;
+pub mod program;
 pub mod verify;
 pub mod version;
@@ -0,0 +1,19 @@
+use crate::{config::Config, server::types::NetworkResponse};
+use axum::{extract::State, Json};
+use std::sync::Arc;
+
+#[utoipa::path(get, path="/networks", responses(
+    (status = 200, description="Networks where programs can be verified", body=Vec<NetworkResponse>)
+))]
+pub async fn networks(State(config): State<Arc<Config>>) -> Json<Vec<NetworkResponse>> {
+    Json(
+        config
+            .networks
+            .iter()
+            .map(|n| NetworkResponse {
+                name: n.name.clone(),
+                genesis_hash: n.genesis_hash.clone(),
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,46 @@
+use crate::{
+    common::Pool,
+    db::{Code, Idl, Program},
+    server::types::{ProgramQueryParams, ProgramResponse},
+    util::validate_and_get_program_id,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+
+#[utoipa::path(get, path="/program", params(ProgramQueryParams), responses(
+    (status = 200, description="Verified code and IDL of the program", body=ProgramResponse)
+))]
+pub async fn program(
+    State(pool): State<Arc<Pool>>,
+    Query(params): Query<ProgramQueryParams>,
+) -> Result<Json<ProgramResponse>, StatusCode> {
+    let conn = &mut pool.get().unwrap();
+
+    let Ok(program_id) = validate_and_get_program_id(&params.id) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let Some(program) = Program::get(conn, &program_id, params.network.as_deref()) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let Some(code) = Code::get(conn, &program.code_id) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let idl = code
+        .idl_hash
+        .as_ref()
+        .and_then(|idl_hash| Idl::get(conn, idl_hash));
+
+    Ok(Json(ProgramResponse {
+        id: program.id,
+        network: program.network,
+        code,
+        idl,
+    }))
+}
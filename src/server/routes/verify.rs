@@ -1,35 +1,126 @@
-use crate::server::error::AppError;
-use crate::server::types::{IdQueryParams, Project, StatusResponse, VerifyRequest, VerifyResponse};
+use crate::server::types::{
+    IdQueryParams, LogsQueryParams, LogsResponse, Project, StatusResponse,
+    VerificationsQueryParams, VerificationsResponse, VerifyRequest, VerifyResponse,
+};
+use crate::server::{
+    auth::Caller,
+    error::{AppError, IdempotencyKeyReused},
+    rate_limit::{client_ip, RateLimited},
+};
 use crate::{
     common::Pool,
-    db::{Verification, VerificationStatus},
-    util::{check_docker_version, generate_id, validate_and_get_code_id},
+    config::Config,
+    db::{ApiScope, SailsVersion, Verification, VerificationFilter, VerificationStatus},
+    fetch_container_name, follow_container_logs, stop_build,
+    util::{
+        blake2_hex, check_docker_version, decode_cursor, encode_cursor, generate_id,
+        get_artifact_path, get_log_file_path, random_alphanumeric, validate_and_get_code_id,
+        validate_and_get_program_id, validate_callback_url, validate_git_ref,
+        validate_idempotency_key,
+    },
 };
+use anyhow::anyhow;
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    Json,
+    extract::{ConnectInfo, Query, State},
+    http::{
+        header::{HeaderName, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
+};
+use diesel::{
+    result::{DatabaseErrorKind, Error as DieselError},
+    Connection, PgConnection,
 };
-use std::{sync::Arc, time::UNIX_EPOCH};
+use futures::{Stream, StreamExt};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+const LOGS_STREAM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const DEFAULT_LIST_LIMIT: i64 = 50;
+const MAX_LIST_LIMIT: i64 = 200;
+
+/// Header with a client-supplied key, requests with the same key create one verification
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header with the token returned by `POST /verify`, allowing to cancel or retry the verification
+const CANCEL_TOKEN_HEADER: &str = "x-cancel-token";
+
+/// Length of the cancel token, returned to the client only once and stored hashed
+const CANCEL_TOKEN_LEN: usize = 40;
 
 #[utoipa::path(post, path="/verify", request_body=VerifyRequest, responses(
-    (status = 200, description="Verification request accepted", body=VerifyResponse)
+    (status = 200, description="Verification request accepted. An equivalent pending, in progress or verified verification is returned instead of creating a new one", body=VerifyResponse),
+    (status = 422, description="Idempotency key was already used with a different request"),
+    (status = 429, description="Too many requests of the client or for the repository, or too many unfinished verifications. Retry after the time in the `Retry-After` header")
 ))]
 pub async fn verify(
     State(pool): State<Arc<Pool>>,
-    Json(VerifyRequest {
+    State(config): State<Arc<Config>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    caller: Option<Extension<Caller>>,
+    headers: HeaderMap,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, AppError> {
+    let idempotency_key = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => {
+            let key = value.to_str().unwrap_or_default();
+            validate_idempotency_key(key)?;
+            Some(key.to_string())
+        }
+        None => None,
+    };
+
+    let submitter = caller.as_ref().and_then(|caller| caller.key_id.clone());
+
+    // Keys are unique per caller: the API key, or the client address of anonymous requests
+    let idempotency_scope = idempotency_key
+        .as_ref()
+        .map(|_| match (&caller, &submitter) {
+            (_, Some(key_id)) => format!("key:{key_id}"),
+            (Some(_), None) => "admin".to_string(),
+            (None, None) => format!(
+                "ip:{}",
+                client_ip(&headers, peer.ip(), config.rate_limit.trust_forwarded_for)
+            ),
+        });
+    let request_hash = blake2_hex(&serde_json::to_vec(&request)?);
+
+    // Retried request returns the verification created by the first one
+    if let (Some(scope), Some(key)) = (&idempotency_scope, &idempotency_key) {
+        if let Some(existing) =
+            existing_by_idempotency_key(&mut pool.get().unwrap(), scope, key, &request_hash)?
+        {
+            return Ok(existing);
+        }
+    }
+
+    let VerifyRequest {
         repo_link,
         code_id,
+        program_id,
         project,
         version,
         network,
         build_idl,
         base_path,
-    }): Json<VerifyRequest>,
-) -> Result<Json<VerifyResponse>, AppError> {
+        git_ref,
+        callback_url,
+    } = request;
+
     let verification_id = generate_id();
+    let cancel_token = random_alphanumeric(CANCEL_TOKEN_LEN);
 
-    check_docker_version(&version)?;
+    check_docker_version(&mut pool.get().unwrap(), &version)?;
 
     let (project_name, manifest_path) = match project.unwrap_or_default() {
         Project::Root => (None, None),
@@ -37,28 +128,240 @@ pub async fn verify(
         Project::ManifestPath(path) => (None, Some(path)),
     };
 
-    let code_id = validate_and_get_code_id(&code_id)?;
-
-    Verification::save(
-        &mut pool.get().unwrap(),
-        Verification {
-            id: verification_id.clone(),
-            repo_link,
-            code_id,
-            project_name,
-            manifest_path,
-            base_path,
-            version,
-            status: VerificationStatus::Pending,
-            network: network.try_into()?,
-            build_idl: build_idl.unwrap_or(true),
-            failed_reason: None,
-            created_at: std::time::SystemTime::now(),
-        },
-    );
-
-    Ok(Json(VerifyResponse {
-        id: verification_id,
+    // The code ID of the program is resolved by the processor
+    let (code_id, program_id) = match (code_id, program_id) {
+        (Some(code_id), None) => (Some(validate_and_get_code_id(&code_id)?), None),
+        (None, Some(program_id)) => (None, Some(validate_and_get_program_id(&program_id)?)),
+        _ => return Err(anyhow!("Either code_id or program_id must be set").into()),
+    };
+
+    if let Some(git_ref) = &git_ref {
+        validate_git_ref(git_ref)?;
+    }
+
+    if let Some(callback_url) = &callback_url {
+        if config.webhooks.secret.is_none() {
+            return Err(anyhow!("Callbacks are disabled").into());
+        }
+        validate_callback_url(callback_url).await?;
+    }
+
+    if config.network(&network).is_none() {
+        let available: Vec<&str> = config.networks.iter().map(|n| n.name.as_str()).collect();
+        return Err(anyhow!(
+            "Unrecognized network name. Available options: {}",
+            available.join(", ")
+        )
+        .into());
+    }
+
+    let verif = Verification {
+        id: verification_id.clone(),
+        repo_link,
+        code_id,
+        project_name,
+        manifest_path,
+        base_path,
+        version,
+        status: VerificationStatus::Pending,
+        network,
+        build_idl: build_idl.unwrap_or(true),
+        failed_reason: None,
+        created_at: std::time::SystemTime::now(),
+        git_ref,
+        commit_sha: None,
+        built_code_len: None,
+        built_code_hash: None,
+        onchain_code_len: None,
+        onchain_code_hash: None,
+        diff_report: None,
+        calculated_code_id: None,
+        wasm_name: None,
+        artifact_sizes: None,
+        program_id,
+        callback_url,
+        attempts: 0,
+        next_attempt_at: None,
+        failure_kind: None,
+        request_hash: idempotency_key.as_ref().map(|_| request_hash.clone()),
+        idempotency_scope: idempotency_scope.clone(),
+        idempotency_key: idempotency_key.clone(),
+        submitter,
+        cancel_token_hash: Some(blake2_hex(cancel_token.as_bytes())),
+    };
+
+    let conn = &mut pool.get().unwrap();
+
+    let saved = conn.transaction(|conn| -> anyhow::Result<VerifyResponse> {
+        Verification::lock_repo(conn, &verif.repo_link)?;
+
+        // The image may have failed since the version was checked
+        SailsVersion::lock_status(conn, &verif.version)?;
+        check_docker_version(conn, &verif.version)?;
+
+        if let Some(existing) = Verification::find_equivalent(conn, &verif) {
+            log::info!("{}: equivalent verification requested", &existing.id);
+            return Ok(VerifyResponse {
+                id: existing.id,
+                existing: true,
+                cancel_token: None,
+            });
+        }
+
+        check_pending_limits(conn, &config, &verif)?;
+
+        Verification::save(conn, verif)?;
+
+        Ok(VerifyResponse {
+            id: verification_id,
+            existing: false,
+            cancel_token: Some(cancel_token),
+        })
+    });
+
+    match (
+        saved,
+        idempotency_scope.as_deref().zip(idempotency_key.as_deref()),
+    ) {
+        (Ok(response), _) => Ok(Json(response)),
+        // Concurrent request with the same idempotency key was saved first
+        (Err(error), Some((scope, key)))
+            if matches!(
+                error.downcast_ref::<DieselError>(),
+                Some(DieselError::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _
+                ))
+            ) =>
+        {
+            existing_by_idempotency_key(conn, scope, key, &request_hash)?
+                .ok_or_else(|| anyhow!("Failed to save verification").into())
+        }
+        (Err(error), _) if !error.is::<DieselError>() => Err(error.into()),
+        (Err(error), _) => Err(anyhow!("Failed to save verification. {error:?}").into()),
+    }
+}
+
+/// Rejects the verification while its repository or code has too many unfinished verifications
+fn check_pending_limits(
+    conn: &mut PgConnection,
+    config: &Config,
+    verif: &Verification,
+) -> anyhow::Result<()> {
+    let limits = &config.rate_limit;
+    let pending_of_repo = Verification::count_unfinished_by_repo(conn, &verif.repo_link)?;
+    let pending_of_code = Verification::count_unfinished_by_code(
+        conn,
+        verif.code_id.as_deref(),
+        verif.program_id.as_deref(),
+    )?;
+
+    if pending_of_repo >= limits.max_pending_per_repo
+        || pending_of_code >= limits.max_pending_per_code
+    {
+        // A slot is freed when an unfinished verification completes, which may take the whole build
+        return Err(RateLimited {
+            retry_after: config.builder.build_timeout(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Whether the request comes from the submitter of the verification, with its API key
+/// or cancel token, or from an admin
+fn is_submitter_or_admin(
+    verif: &Verification,
+    caller: Option<&Caller>,
+    headers: &HeaderMap,
+) -> bool {
+    if caller.is_some_and(|caller| caller.scopes.contains(&ApiScope::Admin)) {
+        return true;
+    }
+
+    let key_id = caller.and_then(|caller| caller.key_id.as_ref());
+    if key_id.is_some_and(|key_id| verif.submitter.as_ref() == Some(key_id)) {
+        return true;
+    }
+
+    headers
+        .get(CANCEL_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|token| verif.cancel_token_hash == Some(blake2_hex(token.as_bytes())))
+}
+
+/// Verification created by an earlier request with the key, which must be the same request
+fn existing_by_idempotency_key(
+    conn: &mut PgConnection,
+    scope: &str,
+    key: &str,
+    request_hash: &str,
+) -> Result<Option<Json<VerifyResponse>>, IdempotencyKeyReused> {
+    let Some(existing) = Verification::get_by_idempotency_key(conn, scope, key) else {
+        return Ok(None);
+    };
+
+    if existing
+        .request_hash
+        .as_deref()
+        .is_some_and(|hash| hash != request_hash)
+    {
+        return Err(IdempotencyKeyReused);
+    }
+
+    Ok(Some(Json(VerifyResponse {
+        id: existing.id,
+        existing: true,
+        cancel_token: None,
+    })))
+}
+
+#[utoipa::path(get, path="/verifications", params(VerificationsQueryParams), responses(
+    (status = 200, description="Verifications matching the filters, newest first", body=VerificationsResponse)
+))]
+pub async fn verifications(
+    State(pool): State<Arc<Pool>>,
+    Query(params): Query<VerificationsQueryParams>,
+) -> Result<Json<VerificationsResponse>, AppError> {
+    let filter = VerificationFilter {
+        code_id: params
+            .code_id
+            .as_deref()
+            .map(validate_and_get_code_id)
+            .transpose()?,
+        repo_link: params.repo_link,
+        status: params.status.as_deref().map(str::parse).transpose()?,
+        network: params.network,
+        version: params.version,
+        created_after: params
+            .created_after
+            .map(|ms| UNIX_EPOCH + Duration::from_millis(ms)),
+        created_before: params
+            .created_before
+            .map(|ms| UNIX_EPOCH + Duration::from_millis(ms)),
+    };
+    let cursor = params.cursor.as_deref().map(decode_cursor).transpose()?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+
+    // One more verification is fetched to know if there is a next page
+    let mut verifs = Verification::list(&mut pool.get().unwrap(), filter, cursor, limit + 1)?;
+
+    let next_cursor = if verifs.len() as i64 > limit {
+        verifs.truncate(limit as usize);
+        verifs
+            .last()
+            .map(|verif| encode_cursor(verif.created_at, &verif.id))
+    } else {
+        None
+    };
+
+    Ok(Json(VerificationsResponse {
+        verifications: verifs.into_iter().map(Into::into).collect(),
+        next_cursor,
     }))
 }
 
@@ -73,21 +376,7 @@ pub async fn status(
     let conn = &mut pool.get().unwrap();
 
     if let Some(verif) = Verification::get(conn, &params.id) {
-        let result = Ok(Json(StatusResponse {
-            status: verif.status.into(),
-            failed_reason: verif.failed_reason,
-            code_id: verif.code_id,
-            repo_link: verif.repo_link,
-            project_name: verif.project_name,
-            base_path: verif.base_path,
-            version: verif.version,
-            manifest_path: verif.manifest_path,
-            created_at: verif
-                .created_at
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_millis(),
-        }));
+        let result = Ok(Json(StatusResponse::from(verif)));
         let time_end = std::time::SystemTime::now();
         let duration = time_end
             .duration_since(time_start)
@@ -101,3 +390,294 @@ pub async fn status(
         Err(StatusCode::NOT_FOUND)
     }
 }
+
+#[utoipa::path(post, path="/verify/retry", params(IdQueryParams), responses(
+    (status = 200, description="Failed verification is queued again", body=StatusResponse),
+    (status = 400, description="Version of the verification is unavailable"),
+    (status = 403, description="Verification was submitted by another client. Only its submitter, holding its API key or cancel token, and admins can retry it"),
+    (status = 404, description="Verification not found"),
+    (status = 409, description="Verification hasn't failed, or an equivalent pending, in progress or verified verification exists"),
+    (status = 429, description="Too many requests of the client, or too many unfinished verifications. Retry after the time in the `Retry-After` header")
+))]
+pub async fn retry(
+    State(pool): State<Arc<Pool>>,
+    State(config): State<Arc<Config>>,
+    caller: Option<Extension<Caller>>,
+    headers: HeaderMap,
+    Query(params): Query<IdQueryParams>,
+) -> Result<Json<StatusResponse>, Response> {
+    let conn = &mut pool.get().unwrap();
+
+    let Some(verif) = Verification::get(conn, &params.id) else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+
+    if !is_submitter_or_admin(&verif, caller.as_deref(), &headers) {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let retried = conn.transaction(|conn| -> anyhow::Result<Result<(), Response>> {
+        Verification::lock_repo(conn, &verif.repo_link)?;
+
+        // Verifications of unavailable versions would never be picked up
+        SailsVersion::lock_status(conn, &verif.version)?;
+        if let Err(error) = check_docker_version(conn, &verif.version) {
+            return Ok(Err(
+                (StatusCode::BAD_REQUEST, error.to_string()).into_response()
+            ));
+        }
+
+        if let Some(existing) = Verification::find_equivalent(conn, &verif) {
+            return Ok(Err((
+                StatusCode::CONFLICT,
+                format!("Equivalent verification {} exists", existing.id),
+            )
+                .into_response()));
+        }
+
+        check_pending_limits(conn, &config, &verif)?;
+
+        if !Verification::retry(conn, &verif.id)? {
+            return Ok(Err(StatusCode::CONFLICT.into_response()));
+        }
+
+        Ok(Ok(()))
+    });
+
+    match retried {
+        Ok(Ok(())) => log::info!("{}: verification queued again", &verif.id),
+        Ok(Err(response)) => return Err(response),
+        Err(error) => return Err(AppError::from(error).into_response()),
+    }
+
+    Verification::get(conn, &verif.id)
+        .map(|verif| Json(verif.into()))
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())
+}
+
+#[utoipa::path(delete, path="/verify", params(IdQueryParams), responses(
+    (status = 200, description="Verification is cancelled", body=StatusResponse),
+    (status = 403, description="Verification was submitted by another client. Only its submitter, holding its API key or cancel token, and admins can cancel it"),
+    (status = 404, description="Verification not found"),
+    (status = 409, description="Verification is already finished")
+))]
+pub async fn cancel(
+    State(pool): State<Arc<Pool>>,
+    caller: Option<Extension<Caller>>,
+    headers: HeaderMap,
+    Query(params): Query<IdQueryParams>,
+) -> Result<Json<StatusResponse>, StatusCode> {
+    let id = params.id.clone();
+    let verif = tokio::task::spawn_blocking(move || {
+        let conn = &mut pool.get().unwrap();
+
+        let Some(verif) = Verification::get(conn, &id) else {
+            return Err(StatusCode::NOT_FOUND);
+        };
+
+        if !is_submitter_or_admin(&verif, caller.as_deref(), &headers) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        match Verification::cancel(conn, &id) {
+            Ok(true) => Verification::get(conn, &id).ok_or(StatusCode::NOT_FOUND),
+            Ok(false) => Err(StatusCode::CONFLICT),
+            Err(error) => {
+                log::error!("{error:?}");
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    log::info!("{}: verification cancelled", &verif.id);
+
+    // The build may have started after the status was read, so the containers are always removed
+    if let Err(error) = stop_build(&verif.id).await {
+        log::error!("{}: Failed to stop the build. {error:?}", &verif.id);
+    }
+
+    Ok(Json(verif.into()))
+}
+
+#[utoipa::path(get, path="/verify/artifact", params(IdQueryParams), responses(
+    (status = 200, description="Built wasm of the verification whose code didn't match the on-chain code", content_type="application/wasm", body=Vec<u8>)
+))]
+pub async fn artifact(
+    State(config): State<Arc<Config>>,
+    Query(params): Query<IdQueryParams>,
+) -> Result<([(HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    // The ID is a part of the file path
+    if !params.id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    match tokio::fs::read(get_artifact_path(&config.builder.artifacts_dir, &params.id)).await {
+        Ok(content) => Ok(([(CONTENT_TYPE, "application/wasm")], content)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            log::error!("{}: Failed to read artifact {error:?}", &params.id);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(get, path="/verify/logs", params(LogsQueryParams), responses(
+    (status = 200, description="Build logs of the verification", body=LogsResponse)
+))]
+pub async fn logs(
+    State(pool): State<Arc<Pool>>,
+    State(config): State<Arc<Config>>,
+    Query(params): Query<LogsQueryParams>,
+) -> Result<Json<LogsResponse>, StatusCode> {
+    let conn = &mut pool.get().unwrap();
+
+    let Some(verif) = Verification::get(conn, &params.id) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let content =
+        match tokio::fs::read(get_log_file_path(&config.builder.logs_dir, &verif.id)).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Err(StatusCode::NOT_FOUND);
+            }
+            Err(error) => {
+                log::error!("{}: Failed to read logs file {error:?}", &verif.id);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+    let content = String::from_utf8_lossy(&content);
+    let lines: Vec<&str> = content.lines().collect();
+    let total_lines = lines.len();
+
+    let offset = match params.tail {
+        Some(tail) => total_lines.saturating_sub(tail),
+        None => params.offset.unwrap_or_default().min(total_lines),
+    };
+    let limit = params.limit.unwrap_or(total_lines);
+
+    Ok(Json(LogsResponse {
+        total_lines,
+        offset,
+        lines: lines
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(String::from)
+            .collect(),
+    }))
+}
+
+fn log_event(line: &str) -> Event {
+    Event::default()
+        .event("log")
+        .data(line.trim_end_matches('\n').replace('\r', ""))
+}
+
+/// Reads the verification on a blocking thread, as the stream polls it for as long as it's open
+async fn get_verification(pool: Arc<Pool>, id: String) -> Option<Verification> {
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().expect("Failed to get connection");
+        Verification::get(&mut conn, &id)
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+fn status_event(verif: Verification) -> Event {
+    Event::default()
+        .event("status")
+        .json_data(StatusResponse::from(verif))
+        .expect("Failed to serialize verification status")
+}
+
+#[utoipa::path(get, path="/verify/logs/stream", params(IdQueryParams), responses(
+    (status = 200, description="Stream of `log` events with the build output and `status` events on status changes. The stream is closed after the final status", content_type="text/event-stream", body=String)
+))]
+pub async fn logs_stream(
+    State(pool): State<Arc<Pool>>,
+    State(config): State<Arc<Config>>,
+    Query(params): Query<IdQueryParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let Some(verif) = get_verification(pool.clone(), params.id).await else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let stream = async_stream::stream! {
+        let id = verif.id;
+        let log_file_path = get_log_file_path(&config.builder.logs_dir, &id);
+        let mut last_status: Option<String> = None;
+        let containers = [fetch_container_name(&id), id.clone()];
+        let mut sent_lines = [0; 2];
+        let mut attempts = verif.attempts;
+        let mut log_file_len = 0;
+
+        loop {
+            let Some(verif) = get_verification(pool.clone(), id.clone()).await else {
+                break;
+            };
+
+            // A requeued verification is built again, recreating its containers and logs file
+            let len = tokio::fs::metadata(&log_file_path)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or_default();
+            if verif.attempts != attempts || len < log_file_len {
+                sent_lines = [0; 2];
+            }
+            attempts = verif.attempts;
+            log_file_len = len;
+
+            if verif.status.is_final() {
+                // The container is removed at this point, the rest of the output is in the logs file
+                if let Ok(content) = tokio::fs::read(&log_file_path).await {
+                    for line in String::from_utf8_lossy(&content).lines().skip(sent_lines.iter().sum()) {
+                        yield Ok(log_event(line));
+                    }
+                }
+                yield Ok(status_event(verif));
+                break;
+            }
+
+            let status: String = verif.status.clone().into();
+            if last_status.as_ref() != Some(&status) {
+                last_status = Some(status);
+                yield Ok(status_event(verif.clone()));
+            }
+
+            if let VerificationStatus::InProgress = verif.status {
+                for (container, sent_lines) in containers.iter().zip(sent_lines.iter_mut()) {
+                    // The compile container is created only after the fetch one has finished
+                    let Ok(logs) = follow_container_logs(container).await else {
+                        break;
+                    };
+                    futures::pin_mut!(logs);
+
+                    // Logs are replayed from the beginning on every attach, so skip lines that were already sent
+                    let mut buf = Vec::new();
+                    let mut line_idx = 0;
+
+                    while let Some(Ok(chunk)) = logs.next().await {
+                        buf.extend(chunk);
+                        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                            let line: Vec<u8> = buf.drain(..=pos).collect();
+                            line_idx += 1;
+                            if line_idx > *sent_lines {
+                                *sent_lines += 1;
+                                yield Ok(log_event(&String::from_utf8_lossy(&line)));
+                            }
+                        }
+                    }
+                }
+            }
+
+            tokio::time::sleep(LOGS_STREAM_POLL_INTERVAL).await;
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
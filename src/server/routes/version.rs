@@ -1,5 +1,6 @@
-use crate::consts::AVAILABLE_VERSIONS;
-use axum::Json;
+use crate::{common::Pool, db::SailsVersion};
+use axum::{extract::State, http::StatusCode, Json};
+use std::sync::Arc;
 
 #[utoipa::path(get, path="/version", responses(
     (status = 200, description="Version of the server", body=String)
@@ -9,8 +10,18 @@ pub async fn version() -> &'static str {
 }
 
 #[utoipa::path(get, path="/supported_versions", responses(
-    (status = 200, description="Supported Sails versions", body=Vec<String>)
+    (status = 200, description="Supported Sails versions and readiness of their verifier images", body=Vec<SailsVersion>)
 ))]
-pub async fn supported_versions() -> Json<Vec<&'static str>> {
-    Json(AVAILABLE_VERSIONS.to_vec())
+pub async fn supported_versions(
+    State(pool): State<Arc<Pool>>,
+) -> Result<Json<Vec<SailsVersion>>, StatusCode> {
+    let conn = &mut pool.get().unwrap();
+
+    match SailsVersion::get_all(conn) {
+        Ok(versions) => Ok(Json(versions)),
+        Err(error) => {
+            log::error!("Failed to get versions from db {error:?}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
@@ -1,8 +1,12 @@
-use crate::db::Code;
+use crate::{
+    db::{ApiScope, Code, CodePrograms, FailureKind, Idl, Verification},
+    WasmDiffReport,
+};
 use serde::{Deserialize, Serialize};
+use std::time::UNIX_EPOCH;
 use utoipa::{IntoParams, ToSchema};
 
-#[derive(Default, Deserialize, Debug, ToSchema)]
+#[derive(Default, Serialize, Deserialize, Debug, ToSchema)]
 pub enum Project {
     /// Package is located in the root directory of the repository
     #[default]
@@ -15,14 +19,22 @@ pub enum Project {
 
 #[derive(Serialize, ToSchema)]
 pub struct StatusResponse {
+    /// ID of the verification
+    pub id: String,
     /// Status of the verification
     pub status: String,
-    /// Reason for failure, if any
+    /// Category of the failure, if any
+    pub failure_kind: Option<FailureKind>,
+    /// Human readable reason for failure, if any
     pub failed_reason: Option<String>,
-    /// Code ID
-    pub code_id: String,
+    /// Code ID. Not set until the code ID of the requested program is resolved
+    pub code_id: Option<String>,
+    /// Requested program ID, if the program was verified by its ID
+    pub program_id: Option<String>,
     /// Repository link
     pub repo_link: String,
+    /// Network where the code is deployed
+    pub network: String,
     /// Version of the Docker image used for verification
     pub version: String,
     /// Project name
@@ -31,17 +43,82 @@ pub struct StatusResponse {
     pub manifest_path: Option<String>,
     /// Base path of the package to be built
     pub base_path: Option<String>,
+    /// Git reference (commit SHA, tag or branch) that was requested to be built
+    pub git_ref: Option<String>,
+    /// Commit SHA of the built source revision
+    pub commit_sha: Option<String>,
+    /// Length of the built wasm in bytes
+    pub built_code_len: Option<i32>,
+    /// Hash of the built wasm
+    pub built_code_hash: Option<String>,
+    /// Length of the original code stored on chain in bytes
+    pub onchain_code_len: Option<i32>,
+    /// Hash of the original code stored on chain
+    pub onchain_code_hash: Option<String>,
+    /// Comparison of the built and the on-chain code, if they differ
+    pub diff_report: Option<WasmDiffReport>,
+    /// Code ID of the built wasm
+    pub calculated_code_id: Option<String>,
+    /// File name of the built wasm
+    pub wasm_name: Option<String>,
+    /// Sizes of the produced files in bytes, keyed by file name
+    #[schema(value_type = Option<HashMap<String, u64>>)]
+    pub artifact_sizes: Option<serde_json::Value>,
     /// Timestamp of the verification
     pub created_at: u128,
 }
 
+impl From<Verification> for StatusResponse {
+    fn from(verif: Verification) -> Self {
+        Self {
+            id: verif.id,
+            status: verif.status.into(),
+            failure_kind: verif.failure_kind,
+            failed_reason: verif.failed_reason,
+            code_id: verif.code_id,
+            program_id: verif.program_id,
+            repo_link: verif.repo_link,
+            network: verif.network,
+            project_name: verif.project_name,
+            base_path: verif.base_path,
+            version: verif.version,
+            manifest_path: verif.manifest_path,
+            git_ref: verif.git_ref,
+            commit_sha: verif.commit_sha,
+            built_code_len: verif.built_code_len,
+            built_code_hash: verif.built_code_hash,
+            onchain_code_len: verif.onchain_code_len,
+            onchain_code_hash: verif.onchain_code_hash,
+            diff_report: verif
+                .diff_report
+                .and_then(|report| serde_json::from_value(report).ok()),
+            calculated_code_id: verif.calculated_code_id,
+            wasm_name: verif.wasm_name,
+            artifact_sizes: verif.artifact_sizes,
+            created_at: verif
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_millis(),
+        }
+    }
+}
+
 #[derive(Deserialize, IntoParams)]
 pub struct IdQueryParams {
     /// ID
     pub id: String,
 }
 
-#[derive(Deserialize, Debug, ToSchema)]
+#[derive(Deserialize, IntoParams)]
+pub struct ProgramQueryParams {
+    /// Program ID
+    pub id: String,
+    /// Network name. If not set, the program resolved most recently on any network is returned
+    pub network: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct VerifyRequest {
     /// Link to the repository containing the code to be verified.
     pub repo_link: String,
@@ -51,18 +128,60 @@ pub struct VerifyRequest {
     pub project: Option<Project>,
     /// Base path of the package to be built (optional, default: root)
     pub base_path: Option<String>,
-    /// Network where the code of the program is deployed
+    /// Name of the network where the code of the program is deployed, see `/networks`
     pub network: String,
-    /// ID of the deployed code
-    pub code_id: String,
+    /// ID of the deployed code. Either `code_id` or `program_id` must be set
+    pub code_id: Option<String>,
+    /// ID of the deployed program, resolved to its code ID
+    pub program_id: Option<String>,
     /// Whether to build the IDL (default: false)
     pub build_idl: Option<bool>,
+    /// Git reference (commit SHA, tag or branch) to build (optional, default: HEAD of the default branch)
+    pub git_ref: Option<String>,
+    /// URL notified with a signed `POST` request when the verification finishes (optional)
+    pub callback_url: Option<String>,
 }
 
 #[derive(serde::Serialize, ToSchema)]
 pub struct VerifyResponse {
     /// ID of the verification
     pub id: String,
+    /// Whether the verification was created by an earlier equivalent request
+    /// or a request with the same idempotency key
+    pub existing: bool,
+    /// Token allowing to cancel or retry the verification, sent in the `X-Cancel-Token` header.
+    /// Returned only when the verification is created
+    pub cancel_token: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct VerificationsQueryParams {
+    /// Code ID
+    pub code_id: Option<String>,
+    /// Repository link
+    pub repo_link: Option<String>,
+    /// Status of the verification: `pending`, `in_progress`, `verified`, `failed` or `cancelled`
+    pub status: Option<String>,
+    /// Network name
+    pub network: Option<String>,
+    /// Sails version
+    pub version: Option<String>,
+    /// Lists verifications created at or after the timestamp in milliseconds
+    pub created_after: Option<u64>,
+    /// Lists verifications created before the timestamp in milliseconds
+    pub created_before: Option<u64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// Maximum number of verifications (default: 50, maximum: 200)
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VerificationsResponse {
+    /// Verifications, newest first
+    pub verifications: Vec<StatusResponse>,
+    /// Cursor of the next page, if there are more verifications
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -78,3 +197,95 @@ pub struct CodesResponseEntry {
     /// Code details
     pub code: Option<Code>,
 }
+
+#[derive(Deserialize, IntoParams)]
+pub struct LogsQueryParams {
+    /// Verification ID
+    pub id: String,
+    /// Number of lines to skip from the beginning of the log (default: 0)
+    pub offset: Option<usize>,
+    /// Maximum number of lines to return (default: all)
+    pub limit: Option<usize>,
+    /// Return only the last N lines of the log. Takes precedence over `offset`
+    pub tail: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LogsResponse {
+    /// Total number of lines in the log
+    pub total_lines: usize,
+    /// Index of the first returned line
+    pub offset: usize,
+    /// Requested lines of the build log
+    pub lines: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterVersionRequest {
+    /// Sails version to support, e.g. `0.8.1`. The `ghcr.io/gear-tech/sails-program-builder`
+    /// image with this tag must exist
+    pub version: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Name of the client the key is issued to
+    pub name: String,
+    /// Scopes granted to the key
+    pub scopes: Vec<ApiScope>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    /// ID of the key, used to revoke it
+    pub id: String,
+    /// API key, sent in the `Authorization: Bearer <key>` header. It can't be retrieved again
+    pub key: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NetworkResponse {
+    /// Name of the network, used in verification requests
+    pub name: String,
+    /// Genesis hash of the network, if configured
+    pub genesis_hash: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProgramResponse {
+    /// Program ID
+    pub id: String,
+    /// Network where the program is deployed
+    pub network: String,
+    /// Verified code of the program
+    pub code: Code,
+    /// IDL of the program, if it was built
+    pub idl: Option<Idl>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CodeProgramsResponse {
+    /// Code ID
+    pub code_id: String,
+    /// Network where the code was verified
+    pub network: String,
+    /// IDs of the active programs running the code
+    pub program_ids: Vec<String>,
+    /// Time the list was fetched from the network, in milliseconds since the Unix epoch
+    pub updated_at: u128,
+}
+
+impl From<&CodePrograms> for CodeProgramsResponse {
+    fn from(programs: &CodePrograms) -> Self {
+        Self {
+            code_id: programs.code_id.clone(),
+            network: programs.network.clone(),
+            program_ids: programs.program_ids.clone(),
+            updated_at: programs
+                .updated_at
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_millis(),
+        }
+    }
+}
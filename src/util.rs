@@ -1,64 +1,235 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::consts::{AVAILABLE_VERSIONS, LOGS_DIR};
+use crate::db::{ImageStatus, SailsVersion};
 use anyhow::{bail, Result};
 use blake2::{digest::typenum::U32, Blake2b, Digest};
+use diesel::PgConnection;
 use rand::{self, distributions::Alphanumeric, thread_rng, Rng};
 
-pub fn generate_id() -> String {
+/// Random string of ASCII letters and digits
+pub fn random_alphanumeric(len: usize) -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
-        .take(15)
+        .take(len)
         .map(char::from)
-        .collect::<String>()
+        .collect()
 }
 
-type Blake2b256 = Blake2b<U32>;
+pub fn generate_id() -> String {
+    random_alphanumeric(15)
+}
 
-pub fn generate_code_id(code: &[u8]) -> String {
-    let mut hasher = Blake2b256::new();
+/// Hex encoded 256 bit Blake2b hash of the data
+pub fn blake2_hex(data: &[u8]) -> String {
+    let mut hasher = Blake2b::<U32>::new();
 
-    hasher.update(code);
+    hasher.update(data);
     hex::encode(hasher.finalize().as_slice())
 }
 
+pub fn generate_code_id(code: &[u8]) -> String {
+    blake2_hex(code)
+}
+
 pub fn hash_idl(idl_data: &str) -> String {
-    let mut hasher = Blake2b::<U32>::new();
+    blake2_hex(idl_data.as_bytes())
+}
 
-    hasher.update(idl_data.as_bytes());
-    hex::encode(hasher.finalize().as_slice())
+/// Generates a new API key, returned to the client only once
+pub fn generate_api_key() -> String {
+    format!("spv_{}", random_alphanumeric(40))
 }
 
-pub fn check_docker_version(version: &str) -> Result<()> {
-    if AVAILABLE_VERSIONS.contains(&version) {
+/// Checks that the version is supported. Verifications of versions whose images are still
+/// being built are accepted and stay pending until the image is ready
+pub fn check_docker_version(conn: &mut PgConnection, version: &str) -> Result<()> {
+    if let Some(v) = SailsVersion::get(conn, version) {
+        if v.status == ImageStatus::Failed {
+            bail!("Version {version} is currently unavailable");
+        }
         Ok(())
     } else {
+        let available_versions: Vec<String> = SailsVersion::get_all(conn)?
+            .into_iter()
+            .filter(|v| v.status != ImageStatus::Failed)
+            .map(|v| v.version)
+            .collect();
         bail!(
             "Unsupported docker version. Available versions: {:?}",
-            AVAILABLE_VERSIONS
+            available_versions
         )
     }
 }
 
+/// Checks that the version has `major.minor.patch[-pre]` format, as it's used as an image tag
+pub fn validate_version(version: &str) -> Result<()> {
+    let (release, pre) = match version.split_once('-') {
+        Some((release, pre)) => (release, Some(pre)),
+        None => (version, None),
+    };
+
+    let is_valid_release = release.split('.').count() == 3
+        && release
+            .split('.')
+            .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    let is_valid_pre = pre.is_none_or(|pre| {
+        !pre.is_empty() && pre.chars().all(|c| c.is_ascii_alphanumeric() || c == '.')
+    });
+
+    if !is_valid_release || !is_valid_pre {
+        bail!("Invalid version");
+    }
+
+    Ok(())
+}
+
 pub fn validate_and_get_code_id(code_id: &str) -> Result<String> {
     let code_id = get_unprefixed_code_id(code_id).unwrap_or(code_id);
 
-    if code_id.len() != 64 {
+    if code_id.len() != 64 || !code_id.chars().all(|c| c.is_ascii_hexdigit()) {
         bail!("Invalid code ID");
     }
 
     Ok(code_id.to_string())
 }
 
+pub fn validate_and_get_program_id(program_id: &str) -> Result<String> {
+    let program_id = program_id.strip_prefix("0x").unwrap_or(program_id);
+
+    if program_id.len() != 64 || !program_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid program ID");
+    }
+
+    Ok(program_id.to_string())
+}
+
+/// Delay before the retry following the given number of failed attempts, starting from
+/// `base_secs` and doubled on every next one. `None` once `max_attempts` are made
+pub fn retry_delay(base_secs: u64, max_attempts: i32, attempts: i32) -> Option<Duration> {
+    if attempts >= max_attempts {
+        return None;
+    }
+
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    Some(Duration::from_secs(base_secs.saturating_mul(1 << exponent)))
+}
+
+/// Whether the address belongs to the verifier's host or a private network
+pub fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal_ip(ip.into()),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Checks that the callback URL is an HTTP(S) URL whose host resolves to public addresses only
+pub async fn validate_callback_url(url: &str) -> Result<()> {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        bail!("Invalid callback URL");
+    };
+
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("Callback URL must be an HTTP(S) URL");
+    }
+
+    let Some(host) = url.host_str() else {
+        bail!("Callback URL must be an HTTP(S) URL");
+    };
+
+    // IPv6 hosts are enclosed in brackets
+    let ips: Vec<IpAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            let port = url.port_or_known_default().unwrap_or_default();
+            let Ok(addrs) = tokio::net::lookup_host((host, port)).await else {
+                bail!("Failed to resolve the callback host");
+            };
+            addrs.map(|addr| addr.ip()).collect()
+        }
+    };
+
+    if ips.is_empty() || ips.into_iter().any(is_internal_ip) {
+        bail!("Callback URL must point to a public address");
+    }
+
+    Ok(())
+}
+
+pub fn validate_idempotency_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > 255 || !key.chars().all(|c| c.is_ascii_graphic()) {
+        bail!("Invalid idempotency key");
+    }
+
+    Ok(())
+}
+
+/// Encodes the position of a listed verification into an opaque cursor
+pub fn encode_cursor(created_at: SystemTime, id: &str) -> String {
+    let micros = created_at
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_micros();
+
+    hex::encode(format!("{micros}:{id}"))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<(SystemTime, String)> {
+    let decoded = hex::decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok());
+
+    let Some((micros, id)) = decoded.as_deref().and_then(|c| c.split_once(':')) else {
+        bail!("Invalid cursor");
+    };
+    let Ok(micros) = micros.parse() else {
+        bail!("Invalid cursor");
+    };
+
+    Ok((UNIX_EPOCH + Duration::from_micros(micros), id.to_string()))
+}
+
+pub fn validate_git_ref(git_ref: &str) -> Result<()> {
+    let is_valid = !git_ref.is_empty()
+        && !git_ref.starts_with('-')
+        && git_ref
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+
+    if !is_valid {
+        bail!("Invalid git ref");
+    }
+
+    Ok(())
+}
+
 pub fn get_unprefixed_code_id(code_id: &str) -> Option<&str> {
     code_id.strip_prefix("0x")
 }
 
-pub fn create_verifier_dockerfile(version: &str) -> Result<()> {
+pub fn create_verifier_dockerfile(version: &str, base_image: &str) -> Result<()> {
     let file_path = format!("Dockerfile-verifier-{version}");
 
     let content = format!(
-        r#"FROM ghcr.io/gear-tech/sails-program-builder:{version}
+        r#"FROM {base_image}:{version}
 WORKDIR /scripts
 COPY build.sh .
 RUN mkdir /mnt/target
@@ -72,14 +243,48 @@ CMD ["/bin/sh", "../scripts/build.sh"]
     Ok(())
 }
 
-pub fn clean_or_create_logs_dir() -> Result<()> {
-    let logs_dir = PathBuf::from(LOGS_DIR);
+pub fn create_logs_dir(logs_dir: &Path) -> Result<()> {
+    fs::create_dir_all(logs_dir)?;
 
-    if logs_dir.exists() {
-        fs::remove_dir_all(&logs_dir)?;
-    }
+    Ok(())
+}
 
-    fs::create_dir_all(&logs_dir)?;
+pub fn create_artifacts_dir(artifacts_dir: &Path) -> Result<()> {
+    fs::create_dir_all(artifacts_dir)?;
 
     Ok(())
 }
+
+/// Removes files of the directory modified more than `max_age` ago.
+/// Returns the number of removed files
+pub fn prune_old_files(dir: &Path, max_age: Duration) -> Result<usize> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+
+        if age > max_age {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Path of the built wasm kept for the verification
+pub fn get_artifact_path(artifacts_dir: &Path, id: &str) -> PathBuf {
+    artifacts_dir.join(format!("{id}.opt.wasm"))
+}
+
+pub fn get_log_file_path(logs_dir: &Path, id: &str) -> PathBuf {
+    logs_dir.join(format!("{id}.log"))
+}
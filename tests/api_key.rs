@@ -0,0 +1,21 @@
+use sails_program_verifier::{
+    db::ApiScope,
+    util::{blake2_hex, generate_api_key},
+};
+
+#[test]
+fn test_api_key() {
+    let key = generate_api_key();
+
+    assert!(key.starts_with("spv_"));
+    assert_ne!(key, generate_api_key());
+    assert_eq!(blake2_hex(key.as_bytes()), blake2_hex(key.as_bytes()));
+    assert_ne!(
+        blake2_hex(key.as_bytes()),
+        blake2_hex(generate_api_key().as_bytes())
+    );
+
+    assert_eq!("submit".parse::<ApiScope>().unwrap(), ApiScope::Submit);
+    assert_eq!(ApiScope::Admin.as_str(), "admin");
+    assert!("write".parse::<ApiScope>().is_err());
+}
@@ -0,0 +1,27 @@
+use sails_program_verifier::util::prune_old_files;
+use std::{
+    fs::{self, File},
+    time::{Duration, SystemTime},
+};
+
+#[test]
+fn test_prune_old_files() {
+    let dir = std::env::temp_dir().join(format!("verifier-prune-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let old = dir.join("old.log");
+    let recent = dir.join("recent.log");
+    File::create(&old)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(3 * 24 * 60 * 60))
+        .unwrap();
+    File::create(&recent).unwrap();
+
+    let removed = prune_old_files(&dir, Duration::from_secs(24 * 60 * 60)).unwrap();
+
+    assert_eq!(removed, 1);
+    assert!(!old.exists());
+    assert!(recent.exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
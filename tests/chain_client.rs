@@ -0,0 +1,158 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use sails_program_verifier::{
+    compare_code, db::FailureKind, ensure_code_onchain, fetch_original_code, resolve_code_id,
+    util::generate_code_id, AppClients, ChainClient, CodeInfo, MemoryClient, ProgramState,
+};
+use std::sync::Arc;
+
+/// Client of a node that doesn't respond
+struct UnavailableClient;
+
+#[async_trait]
+impl ChainClient for UnavailableClient {
+    fn genesis_hash(&self) -> String {
+        "01".into()
+    }
+
+    async fn original_code(&self, _code_id: &str) -> Result<Option<Vec<u8>>> {
+        bail!("Node is unavailable")
+    }
+
+    async fn code_metadata(&self, _code_id: &str) -> Result<Option<CodeInfo>> {
+        bail!("Node is unavailable")
+    }
+
+    async fn program_state(&self, _program_id: &str) -> Result<Option<ProgramState>> {
+        bail!("Node is unavailable")
+    }
+
+    async fn code_programs(&self, _code_id: &str) -> Result<Vec<String>> {
+        bail!("Node is unavailable")
+    }
+}
+
+#[tokio::test]
+async fn test_memory_client() {
+    let code = b"\0asm\x01\0\0\0".to_vec();
+    let code_id = generate_code_id(&code);
+    let info = CodeInfo {
+        author: "00".repeat(32),
+        block_number: 42,
+    };
+
+    let program_id = "aa".repeat(32);
+
+    let mut clients = AppClients::default();
+    clients.set(
+        "local",
+        Arc::new(
+            MemoryClient::new("0x01")
+                .with_code(code.clone(), info.clone())
+                .with_program(&program_id, &code_id),
+        ),
+    );
+
+    let client = clients.get("local").unwrap();
+
+    assert_eq!(client.genesis_hash(), "01");
+    assert!(client.check_code_onchain(&code_id).await.unwrap());
+    assert_eq!(client.original_code(&code_id).await.unwrap(), Some(code));
+    assert_eq!(client.code_metadata(&code_id).await.unwrap(), Some(info));
+    assert_eq!(
+        client.program_state(&program_id).await.unwrap(),
+        Some(ProgramState::Active(code_id.clone()))
+    );
+    assert_eq!(
+        client.code_programs(&code_id).await.unwrap(),
+        vec![program_id.clone()]
+    );
+
+    let unknown_id = "ff".repeat(32);
+    assert!(!client.check_code_onchain(&unknown_id).await.unwrap());
+    assert_eq!(client.program_state(&unknown_id).await.unwrap(), None);
+    assert!(client.original_code("invalid").await.is_err());
+    assert!(clients.get("vara_mainnet").is_err());
+}
+
+#[tokio::test]
+async fn test_chain_checks() {
+    let code = b"\0asm\x01\0\0\0\x01".to_vec();
+    let code_id = generate_code_id(&code);
+    let program_id = "aa".repeat(32);
+    let info = CodeInfo {
+        author: "00".repeat(32),
+        block_number: 1,
+    };
+
+    let exited_id = "cc".repeat(32);
+
+    let client = MemoryClient::new("0x01")
+        .with_code(code.clone(), info)
+        .with_program(&program_id, &code_id)
+        .with_program_state(&exited_id, ProgramState::Exited);
+
+    assert_eq!(
+        resolve_code_id(&client, &program_id).await,
+        Ok(code_id.clone())
+    );
+    let failure = resolve_code_id(&client, &"bb".repeat(32))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::CodeNotOnchain);
+    assert!(!failure.transient);
+    // Exited programs never become active again
+    let failure = resolve_code_id(&client, &exited_id).await.unwrap_err();
+    assert_eq!(failure.kind, FailureKind::CodeNotOnchain);
+    assert_eq!(failure.reason, "Program has exited");
+    assert!(!failure.transient);
+    // Invalid IDs can't be fixed by retrying
+    let failure = resolve_code_id(&client, "invalid").await.unwrap_err();
+    assert_eq!(failure.kind, FailureKind::CodeNotOnchain);
+    assert_eq!(failure.reason, "Invalid program ID");
+    assert!(!failure.transient);
+    let failure = ensure_code_onchain(&client, &"zz".repeat(32))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.reason, "Invalid code ID");
+    assert!(!failure.transient);
+    // Errors of the node are retried
+    let failure = resolve_code_id(&UnavailableClient, &program_id)
+        .await
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::Internal);
+    assert!(failure.transient);
+    let failure = fetch_original_code(&UnavailableClient, &code_id)
+        .await
+        .unwrap_err();
+    assert!(failure.transient);
+
+    assert_eq!(ensure_code_onchain(&client, &code_id).await, Ok(()));
+    let failure = ensure_code_onchain(&client, &"ff".repeat(32))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::CodeNotOnchain);
+    assert!(!failure.transient);
+
+    let onchain = fetch_original_code(&client, &code_id).await.unwrap();
+    assert_eq!(compare_code(&code, onchain.as_deref()), None);
+
+    let mut built = code.clone();
+    built[8] = 2;
+    assert_eq!(
+        compare_code(&built, onchain.as_deref()).unwrap(),
+        "Built code differs from the on-chain code at byte 8 (built: 9 bytes, on-chain: 9 bytes)"
+    );
+    assert_eq!(
+        compare_code(&code[..8], onchain.as_deref()).unwrap(),
+        "Built code differs from the on-chain code at byte 8 (built: 8 bytes, on-chain: 9 bytes)"
+    );
+
+    let missing = fetch_original_code(&client, &"ff".repeat(32))
+        .await
+        .unwrap();
+    assert_eq!(
+        compare_code(&code, missing.as_deref()).unwrap(),
+        "Original code is not found on chain"
+    );
+}
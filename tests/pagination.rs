@@ -0,0 +1,17 @@
+use sails_program_verifier::util::{decode_cursor, encode_cursor};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_cursor() {
+    let created_at = UNIX_EPOCH + Duration::from_micros(1_718_000_000_123_456);
+
+    let cursor = encode_cursor(created_at, "aBc123");
+    assert_eq!(
+        decode_cursor(&cursor).unwrap(),
+        (created_at, "aBc123".to_string())
+    );
+
+    assert!(decode_cursor("not a cursor").is_err());
+    assert!(decode_cursor(&hex::encode("123")).is_err());
+    assert!(decode_cursor(&hex::encode("abc:id")).is_err());
+}
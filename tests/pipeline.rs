@@ -0,0 +1,319 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use sails_program_verifier::{
+    config::ProcessorConfig,
+    db::{BuildResult, CodeComparison, FailureKind, Verification, VerificationStatus},
+    process_verification,
+    util::generate_code_id,
+    AppClients, Build, BuildArtifacts, CodeInfo, MemoryClient, MemoryStore, ProjectBuilder,
+    VerificationStore,
+};
+use std::{collections::BTreeMap, sync::Arc, sync::Mutex, time::SystemTime};
+
+const COMMIT_SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+/// Builder producing the given code without running Docker
+struct FakeBuilder {
+    code: Vec<u8>,
+    built: Mutex<bool>,
+    kept: Mutex<Vec<String>>,
+}
+
+impl FakeBuilder {
+    fn new(code: &[u8]) -> Self {
+        Self {
+            code: code.to_vec(),
+            built: Mutex::new(false),
+            kept: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl ProjectBuilder for FakeBuilder {
+    async fn build(&self, _verif: &Verification) -> Build {
+        *self.built.lock().unwrap() = true;
+
+        Build {
+            artifacts: Ok(BuildArtifacts {
+                code: self.code.clone(),
+                code_id: generate_code_id(&self.code),
+                idl: Some("service Counter {}".into()),
+                name: "counter".into(),
+                wasm_name: "counter.opt.wasm".into(),
+                sizes: BTreeMap::from([("counter.opt.wasm".into(), self.code.len() as u64)]),
+            }),
+            commit_sha: Some(COMMIT_SHA.into()),
+        }
+    }
+
+    fn keep_artifact(&self, verif_id: &str, _code: &[u8]) -> Result<()> {
+        self.kept.lock().unwrap().push(verif_id.to_string());
+        Ok(())
+    }
+}
+
+/// Store losing its connection once the project is built
+struct FlakyStore(MemoryStore);
+
+#[async_trait]
+impl VerificationStore for FlakyStore {
+    async fn set_program_code(
+        &self,
+        verif: &Verification,
+        program_id: &str,
+        code_id: &str,
+    ) -> Result<()> {
+        self.0.set_program_code(verif, program_id, code_id).await
+    }
+
+    async fn start(&self, verif: &Verification) -> Result<()> {
+        self.0.start(verif).await
+    }
+
+    async fn fail(&self, verif_id: &str, kind: FailureKind, reason: String) -> Result<()> {
+        self.0.fail(verif_id, kind, reason).await
+    }
+
+    async fn requeue(
+        &self,
+        verif_id: &str,
+        kind: FailureKind,
+        reason: String,
+        next_attempt_at: SystemTime,
+    ) -> Result<()> {
+        self.0
+            .requeue(verif_id, kind, reason, next_attempt_at)
+            .await
+    }
+
+    async fn set_commit_sha(&self, verif_id: &str, commit_sha: &str) -> Result<()> {
+        self.0.set_commit_sha(verif_id, commit_sha).await
+    }
+
+    async fn set_build_result(&self, _verif_id: &str, _result: BuildResult) -> Result<()> {
+        bail!("Connection lost")
+    }
+
+    async fn set_code_comparison(&self, verif_id: &str, comparison: CodeComparison) -> Result<()> {
+        self.0.set_code_comparison(verif_id, comparison).await
+    }
+
+    async fn complete(
+        &self,
+        verif: &Verification,
+        artifacts: BuildArtifacts,
+        commit_sha: Option<String>,
+    ) -> Result<bool> {
+        self.0.complete(verif, artifacts, commit_sha).await
+    }
+}
+
+fn verification(id: &str, code_id: Option<&str>, program_id: Option<&str>) -> Verification {
+    Verification {
+        id: id.into(),
+        repo_link: "https://github.com/gear-foundation/counter".into(),
+        code_id: code_id.map(Into::into),
+        project_name: None,
+        manifest_path: None,
+        build_idl: true,
+        version: "0.8.1".into(),
+        status: VerificationStatus::Pending,
+        network: "local".into(),
+        failed_reason: None,
+        created_at: SystemTime::now(),
+        base_path: None,
+        git_ref: None,
+        commit_sha: None,
+        built_code_len: None,
+        built_code_hash: None,
+        onchain_code_len: None,
+        onchain_code_hash: None,
+        diff_report: None,
+        calculated_code_id: None,
+        wasm_name: None,
+        artifact_sizes: None,
+        program_id: program_id.map(Into::into),
+        callback_url: None,
+        idempotency_key: None,
+        attempts: 0,
+        next_attempt_at: None,
+        failure_kind: None,
+        idempotency_scope: None,
+        request_hash: None,
+        submitter: None,
+        cancel_token_hash: None,
+    }
+}
+
+fn clients(client: MemoryClient) -> AppClients {
+    let mut clients = AppClients::default();
+    clients.set("local", Arc::new(client));
+    clients
+}
+
+fn code_info() -> CodeInfo {
+    CodeInfo {
+        author: "00".repeat(32),
+        block_number: 42,
+    }
+}
+
+#[tokio::test]
+async fn test_verified() {
+    let code = b"\0asm\x01\0\0\0".to_vec();
+    let code_id = generate_code_id(&code);
+    let program_id = "aa".repeat(32);
+
+    let clients = clients(
+        MemoryClient::new("0x01")
+            .with_code(code.clone(), code_info())
+            .with_program(&program_id, &code_id),
+    );
+    let store =
+        MemoryStore::default().with_verification(verification("v1", None, Some(&program_id)));
+    let builder = FakeBuilder::new(&code);
+
+    process_verification(
+        &store,
+        &builder,
+        &clients,
+        &ProcessorConfig::default(),
+        store.verification("v1").unwrap(),
+    )
+    .await;
+
+    let verif = store.verification("v1").unwrap();
+    assert_eq!(verif.status, VerificationStatus::Verified);
+    assert_eq!(verif.code_id.as_deref(), Some(code_id.as_str()));
+    assert_eq!(verif.commit_sha.as_deref(), Some(COMMIT_SHA));
+    assert_eq!(verif.calculated_code_id.as_deref(), Some(code_id.as_str()));
+    assert_eq!(verif.onchain_code_len, Some(code.len() as i32));
+    assert!(verif.diff_report.is_none());
+    assert!(builder.kept.lock().unwrap().is_empty());
+
+    let saved = store.code(&code_id).unwrap();
+    assert_eq!(saved.name, "counter");
+    assert_eq!(saved.commit_sha.as_deref(), Some(COMMIT_SHA));
+    assert!(saved.idl_hash.is_some());
+}
+
+#[tokio::test]
+async fn test_mismatch() {
+    let onchain = b"\0asm\x01\0\0\0".to_vec();
+    let built = b"\0asm\x01\0\0\0\x01".to_vec();
+    let code_id = generate_code_id(&onchain);
+
+    let clients = clients(MemoryClient::new("0x01").with_code(onchain.clone(), code_info()));
+    let store = MemoryStore::default().with_verification(verification("v1", Some(&code_id), None));
+    let builder = FakeBuilder::new(&built);
+
+    process_verification(
+        &store,
+        &builder,
+        &clients,
+        &ProcessorConfig::default(),
+        store.verification("v1").unwrap(),
+    )
+    .await;
+
+    let verif = store.verification("v1").unwrap();
+    assert_eq!(verif.status, VerificationStatus::Failed);
+    assert_eq!(verif.failure_kind, Some(FailureKind::CodeMismatch));
+    assert_eq!(verif.failed_reason.as_deref(), Some("Code ID mismatch"));
+    assert_eq!(verif.calculated_code_id, Some(generate_code_id(&built)));
+    assert_eq!(verif.built_code_len, Some(built.len() as i32));
+    assert_eq!(verif.onchain_code_len, Some(onchain.len() as i32));
+    assert_eq!(*builder.kept.lock().unwrap(), vec!["v1".to_string()]);
+    assert!(store.code(&code_id).is_none());
+}
+
+#[tokio::test]
+async fn test_code_not_onchain() {
+    let code = b"\0asm\x01\0\0\0".to_vec();
+    let code_id = generate_code_id(&code);
+
+    let clients = clients(MemoryClient::new("0x01"));
+    let store = MemoryStore::default().with_verification(verification("v1", Some(&code_id), None));
+    let builder = FakeBuilder::new(&code);
+
+    process_verification(
+        &store,
+        &builder,
+        &clients,
+        &ProcessorConfig::default(),
+        store.verification("v1").unwrap(),
+    )
+    .await;
+
+    let verif = store.verification("v1").unwrap();
+    assert_eq!(verif.status, VerificationStatus::Failed);
+    assert_eq!(verif.failure_kind, Some(FailureKind::CodeNotOnchain));
+    assert_eq!(
+        verif.failed_reason.as_deref(),
+        Some("Code doesn't exist on chain")
+    );
+    assert!(!*builder.built.lock().unwrap());
+    assert!(store.code(&code_id).is_none());
+}
+
+#[tokio::test]
+async fn test_cancelled() {
+    let code = b"\0asm\x01\0\0\0".to_vec();
+    let code_id = generate_code_id(&code);
+
+    let clients = clients(MemoryClient::new("0x01").with_code(code.clone(), code_info()));
+    let mut verif = verification("v1", Some(&code_id), None);
+    verif.status = VerificationStatus::Cancelled;
+    let store = MemoryStore::default().with_verification(verif.clone());
+    let builder = FakeBuilder::new(&code);
+
+    process_verification(
+        &store,
+        &builder,
+        &clients,
+        &ProcessorConfig::default(),
+        verif,
+    )
+    .await;
+
+    assert_eq!(
+        store.verification("v1").unwrap().status,
+        VerificationStatus::Cancelled
+    );
+    assert!(!*builder.built.lock().unwrap());
+    assert!(store.code(&code_id).is_none());
+}
+
+#[tokio::test]
+async fn test_unexpected_error() {
+    let code = b"\0asm\x01\0\0\0".to_vec();
+    let code_id = generate_code_id(&code);
+
+    let clients = clients(MemoryClient::new("0x01").with_code(code.clone(), code_info()));
+    let store = FlakyStore(MemoryStore::default().with_verification(verification(
+        "v1",
+        Some(&code_id),
+        None,
+    )));
+    let builder = FakeBuilder::new(&code);
+
+    process_verification(
+        &store,
+        &builder,
+        &clients,
+        &ProcessorConfig::default(),
+        store.0.verification("v1").unwrap(),
+    )
+    .await;
+
+    // The verification isn't left in progress
+    let verif = store.0.verification("v1").unwrap();
+    assert_eq!(verif.status, VerificationStatus::Failed);
+    assert_eq!(verif.failure_kind, Some(FailureKind::Internal));
+    assert_eq!(
+        verif.failed_reason.as_deref(),
+        Some("Internal error while processing the verification")
+    );
+    assert!(store.0.code(&code_id).is_none());
+}
@@ -0,0 +1,24 @@
+use sails_program_verifier::TokenBuckets;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_token_buckets() {
+    let buckets = TokenBuckets::new(2, Duration::from_secs(10));
+    let now = Instant::now();
+
+    assert!(buckets.take("a", now).is_ok());
+    assert!(buckets.take("a", now).is_ok());
+
+    let limited = buckets.take("a", now).unwrap_err();
+    assert_eq!(limited.retry_after, Duration::from_secs(10));
+
+    // Buckets of other keys are independent
+    assert!(buckets.take("b", now).is_ok());
+
+    let later = now + Duration::from_secs(5);
+    let limited = buckets.take("a", later).unwrap_err();
+    assert_eq!(limited.retry_after, Duration::from_secs(5));
+
+    assert!(buckets.take("a", now + Duration::from_secs(10)).is_ok());
+    assert!(buckets.take("a", now + Duration::from_secs(10)).is_err());
+}
@@ -0,0 +1,33 @@
+use sails_program_verifier::util::retry_delay;
+use std::time::Duration;
+
+#[test]
+fn test_retry_delay() {
+    let delays: Vec<_> = (1..=4)
+        .map(|attempts| retry_delay(60, 5, attempts))
+        .collect();
+    assert_eq!(
+        delays,
+        [60, 120, 240, 480].map(|secs| Some(Duration::from_secs(secs)))
+    );
+
+    // The exponent is capped, so the delay doesn't overflow
+    assert_eq!(
+        retry_delay(1, i32::MAX, 100),
+        Some(Duration::from_secs(1 << 16))
+    );
+    assert_eq!(
+        retry_delay(u64::MAX, i32::MAX, 3),
+        Some(Duration::from_secs(u64::MAX))
+    );
+}
+
+#[test]
+fn test_retry_delay_max_attempts() {
+    assert_eq!(retry_delay(10, 3, 2), Some(Duration::from_secs(20)));
+    assert_eq!(retry_delay(10, 3, 3), None);
+    assert_eq!(retry_delay(10, 3, 4), None);
+
+    // A single attempt is never retried
+    assert_eq!(retry_delay(10, 1, 1), None);
+}
@@ -0,0 +1,64 @@
+use sails_program_verifier::util::{
+    is_internal_ip, validate_and_get_code_id, validate_and_get_program_id, validate_callback_url,
+};
+
+#[test]
+fn test_validate_code_id() {
+    let id = "cd".repeat(32);
+
+    assert_eq!(validate_and_get_code_id(&id).unwrap(), id);
+    assert_eq!(validate_and_get_code_id(&format!("0x{id}")).unwrap(), id);
+
+    assert!(validate_and_get_code_id(&id[2..]).is_err());
+    assert!(validate_and_get_code_id(&format!("{}zz", &id[2..])).is_err());
+}
+
+#[test]
+fn test_validate_program_id() {
+    let id = "ab".repeat(32);
+
+    assert_eq!(validate_and_get_program_id(&id).unwrap(), id);
+    assert_eq!(validate_and_get_program_id(&format!("0x{id}")).unwrap(), id);
+
+    assert!(validate_and_get_program_id(&id[2..]).is_err());
+    assert!(validate_and_get_program_id(&format!("{}zz", &id[2..])).is_err());
+    assert!(validate_and_get_program_id(&format!("{}ё", &id[3..])).is_err());
+}
+
+#[test]
+fn test_is_internal_ip() {
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(is_internal_ip(ip.parse().unwrap()), "{ip}");
+    }
+
+    for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111"] {
+        assert!(!is_internal_ip(ip.parse().unwrap()), "{ip}");
+    }
+}
+
+#[tokio::test]
+async fn test_validate_callback_url() {
+    assert!(validate_callback_url("https://1.1.1.1/hook").await.is_ok());
+
+    for url in [
+        "ftp://1.1.1.1/hook",
+        "http://127.0.0.1:8080/hook",
+        "http://169.254.169.254/latest/meta-data",
+        "http://10.0.0.1/hook",
+        "http://[::1]/hook",
+        "http://localhost/hook",
+    ] {
+        assert!(validate_callback_url(url).await.is_err(), "{url}");
+    }
+}
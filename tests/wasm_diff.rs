@@ -0,0 +1,38 @@
+use sails_program_verifier::diff_wasm;
+
+/// Module with a single function without params exported under the name
+fn module(export: u8, body: &[u8]) -> Vec<u8> {
+    let mut code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    // Type, function and export sections
+    code.extend([0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+    code.extend([0x03, 0x02, 0x01, 0x00]);
+    code.extend([0x07, 0x05, 0x01, 0x01, export, 0x00, 0x00]);
+    // Code section with a body without locals
+    let body_len = body.len() as u8 + 2;
+    code.extend([0x0a, body_len + 2, 0x01, body_len, 0x00]);
+    code.extend(body);
+    code.push(0x0b);
+    code
+}
+
+#[test]
+fn test_diff_wasm() {
+    let built = module(b'a', &[]);
+    let onchain = module(b'b', &[0x01]);
+
+    let report = diff_wasm(&built, &onchain).unwrap();
+
+    let sections: Vec<_> = report.sections.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(sections, ["export", "code"]);
+    assert_eq!(report.sections[1].built_size, Some(4));
+    assert_eq!(report.sections[1].onchain_size, Some(5));
+    assert_eq!(report.exports.only_built, ["a"]);
+    assert_eq!(report.exports.only_onchain, ["b"]);
+    assert!(report.imports.only_built.is_empty());
+    assert_eq!(report.functions.built_count, 1);
+    assert_eq!(report.functions.differing_bodies, 1);
+
+    let report = diff_wasm(&built, &built).unwrap();
+    assert!(report.sections.is_empty());
+    assert_eq!(report.functions.differing_bodies, 0);
+}
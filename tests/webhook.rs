@@ -0,0 +1,10 @@
+use sails_program_verifier::sign_payload;
+
+#[test]
+fn test_sign_payload() {
+    // RFC 4231, test case 2
+    assert_eq!(
+        sign_payload("Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}